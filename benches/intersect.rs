@@ -0,0 +1,61 @@
+use std::hint::black_box;
+use std::time::Instant;
+use modppl::prelude::*;
+use modppl_derender::*;
+
+
+const NUM_RAYS: usize = 1 << 16;
+const NUM_REPS: usize = 50;
+
+fn random_rays(n: usize) -> RayPacket {
+    let mut rng = ThreadRng::default();
+    let mut rays = RayPacket::with_capacity(n);
+    for _ in 0..n {
+        let o = [
+            uniform.random(&mut rng, (-2.0, 2.0)) as f32,
+            uniform.random(&mut rng, (0.1, 2.0)) as f32,
+            uniform.random(&mut rng, (-2.0, 2.0)) as f32
+        ];
+        let mut d = [
+            normal.random(&mut rng, (0.0, 1.0)) as f32,
+            normal.random(&mut rng, (0.0, 1.0)) as f32,
+            normal.random(&mut rng, (0.0, 1.0)) as f32
+        ];
+        vec3_normalize(&mut d);
+        rays.push(o, d);
+    }
+    rays
+}
+
+fn bench(name: &str, mut f: impl FnMut()) -> f64 {
+    f();
+    let start = Instant::now();
+    for _ in 0..NUM_REPS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() as f64 / (NUM_REPS * NUM_RAYS) as f64;
+    println!("{name:<36} {ns:>8.2} ns/ray");
+    ns
+}
+
+fn main() {
+    let rays = random_rays(NUM_RAYS);
+    let solids: Vec<(&str, Box<dyn Solid>)> = vec![
+        ("plane", Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] })),
+        ("sphere", Box::new(Sphere { center: [0.3, 0.5, -0.2], radius: 0.5 }))
+    ];
+
+    for (name, s) in solids.iter() {
+        let scalar = bench(&format!("{name} ray_intersect_reflect"), || {
+            for i in 0..rays.len() {
+                black_box(s.ray_intersect_reflect(rays.origin(i), rays.dir(i)));
+            }
+        });
+        let mut hits = PacketHits::new(rays.len());
+        let packet = bench(&format!("{name} ray_intersect_reflect_packet"), || {
+            s.ray_intersect_reflect_packet(black_box(&rays), &mut hits);
+            black_box(&hits);
+        });
+        println!("{name:<36} {:>8.2}x speedup", scalar / packet);
+    }
+}
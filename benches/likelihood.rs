@@ -0,0 +1,43 @@
+use std::hint::black_box;
+use std::time::Instant;
+use modppl::prelude::*;
+use modppl_derender::*;
+
+
+const NUM_REPS: usize = 20;
+
+fn bench(name: &str, mut f: impl FnMut()) -> f64 {
+    f();
+    let start = Instant::now();
+    for _ in 0..NUM_REPS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() as f64 / (NUM_REPS * AREA) as f64;
+    println!("{name:<36} {ns:>8.2} ns/pixel");
+    ns
+}
+
+fn main() {
+    let mut rng = ThreadRng::default();
+    let colors = load_colors("./tests/ball.bmp");
+    let noisy = noisy_colors.random(&mut rng, (colors.clone(), 0.1, 0.1));
+
+    for noise in [PixelNoise::Gaussian, PixelNoise::Laplace, PixelNoise::StudentT(3.0)] {
+        let pixel = NoisyPixel { noise };
+        let likelihood = NoisyColors { noise, mask: Mask::All };
+        let name = format!("{noise:?}");
+        let per_pixel = bench(&format!("{name} per-pixel logpdf"), || {
+            let mut w = 0.;
+            for i in 0..AREA {
+                for c in 0..=2 {
+                    w += pixel.logpdf(&noisy[i][c], (colors[i][c], 0.1, 0.1));
+                }
+            }
+            black_box(w);
+        });
+        let image = bench(&format!("{name} image logpdf"), || {
+            black_box(likelihood.logpdf(black_box(&noisy), (colors.clone(), 0.1, 0.1)));
+        });
+        println!("{name:<36} {:>8.2}x speedup", per_pixel / image);
+    }
+}
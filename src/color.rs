@@ -0,0 +1,129 @@
+use std::f32::consts::PI;
+use crate::types::*;
+use crate::linear::*;
+
+
+/* color spaces */
+
+/// D65 reference white in CIE XYZ
+const WHITE: Vec3 = [0.95047, 1.0, 1.08883];
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 { 12.92 * v } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA { t.cbrt() } else { t / (3.0 * DELTA * DELTA) + 4.0 / 29.0 }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA { t * t * t } else { 3.0 * DELTA * DELTA * (t - 4.0 / 29.0) }
+}
+
+/// converts a (BGR, sRGB-encoded) color to CIELAB `[L, a, b]` under a D65 white, with `L` in [0, 100]
+pub fn bgr_to_lab(c: Color) -> Vec3 {
+    let (r, g, b) = (srgb_to_linear(c[2]), srgb_to_linear(c[1]), srgb_to_linear(c[0]));
+    let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.072175 * b;
+    let z = 0.0193339 * r + 0.119192 * g + 0.9503041 * b;
+    let (fx, fy, fz) = (lab_f(x / WHITE[0]), lab_f(y / WHITE[1]), lab_f(z / WHITE[2]));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// converts a CIELAB color back to BGR, without clamping colors outside the sRGB gamut
+pub fn lab_to_bgr(lab: Vec3) -> Color {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let (fx, fz) = (fy + lab[1] / 500.0, fy - lab[2] / 200.0);
+    let (x, y, z) = (WHITE[0] * lab_f_inv(fx), WHITE[1] * lab_f_inv(fy), WHITE[2] * lab_f_inv(fz));
+    let r =  3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+    let g = -0.969266 * x + 1.8760108 * y + 0.041556 * z;
+    let b =  0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+    [linear_to_srgb(b), linear_to_srgb(g), linear_to_srgb(r)]
+}
+
+/// converts a BGR color to `[h, s, v]`, with the hue `h` in [0, 1)
+pub fn bgr_to_hsv(c: Color) -> Vec3 {
+    let (r, g, b) = (c[2], c[1], c[0]);
+    let v = r.max(g).max(b);
+    let chroma = v - r.min(g).min(b);
+    if chroma <= 0.0 {
+        return [0.0, 0.0, v];
+    }
+    let h = if v == r {
+        (g - b) / chroma
+    } else if v == g {
+        2.0 + (b - r) / chroma
+    } else {
+        4.0 + (r - g) / chroma
+    };
+    [(h / 6.0).rem_euclid(1.0), chroma / v, v]
+}
+
+/// converts `[h, s, v]` back to a BGR color
+pub fn hsv_to_bgr(hsv: Vec3) -> Color {
+    let [h, s, v] = hsv;
+    let channel = |n: f32| {
+        let k = (n + 6.0 * h).rem_euclid(6.0);
+        v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    [channel(1.0), channel(3.0), channel(5.0)]
+}
+
+/// luma (Rec. 601) of a BGR color
+pub fn luma(c: Color) -> f32 {
+    0.114 * c[0] + 0.587 * c[1] + 0.299 * c[2]
+}
+
+/// a color space in which observations are scored as a lightness and two chroma coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// CIELAB, scaled to `[L/100, a/100, b/100]`
+    Lab,
+    /// the HSV cone `[v, s v cos(2 pi h), s v sin(2 pi h)]`, which is continuous across gray and the hue wrap
+    Hsv
+}
+
+impl ColorSpace {
+    /// lightness in about [0, 1] and chroma coordinates in about [-1, 1] of a BGR color
+    pub fn coords(&self, c: Color) -> Vec3 {
+        match self {
+            ColorSpace::Lab => {
+                let lab = bgr_to_lab(c);
+                [lab[0] / 100.0, lab[1] / 100.0, lab[2] / 100.0]
+            }
+            ColorSpace::Hsv => {
+                let [h, s, v] = bgr_to_hsv(c);
+                let angle = 2.0 * PI * h;
+                [v, s * v * angle.cos(), s * v * angle.sin()]
+            }
+        }
+    }
+
+    /// bounds of the coordinates of all valid BGR colors
+    pub fn ranges(&self) -> [(f32, f32); 3] {
+        match self {
+            ColorSpace::Lab => [(0.0, 1.0), (-0.87, 0.99), (-1.08, 0.95)],
+            ColorSpace::Hsv => [(0.0, 1.0), (-1.0, 1.0), (-1.0, 1.0)]
+        }
+    }
+
+    /// inverse of `coords`, clamped to valid BGR colors
+    pub fn color(&self, coords: Vec3) -> Color {
+        let c = match self {
+            ColorSpace::Lab => lab_to_bgr(vec3_scale(&coords, 100.0)),
+            ColorSpace::Hsv => {
+                let v = coords[0].clamp(0.0, 1.0);
+                let s = if v > 0.0 { (coords[1].hypot(coords[2]) / v).min(1.0) } else { 0.0 };
+                let h = coords[2].atan2(coords[1]) / (2.0 * PI);
+                hsv_to_bgr([h.rem_euclid(1.0), s, v])
+            }
+        };
+        [c[0].clamp(0.0, 1.0), c[1].clamp(0.0, 1.0), c[2].clamp(0.0, 1.0)]
+    }
+}
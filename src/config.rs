@@ -10,4 +10,6 @@ pub const VIEWPORT: [f32; 4] = [0.0, 0.0, W as f32, H as f32];
 
 pub const FOVY: f32 = PI/2.0;
 pub const NEAR: f32 = 0.2;
-pub const FAR : f32 = 7.5;
\ No newline at end of file
+pub const FAR : f32 = 7.5;
+pub const OUTLIER_PROB: f32 = 0.1;
+pub const PIXEL_NOISE : f32 = 0.1;
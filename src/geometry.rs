@@ -0,0 +1,274 @@
+use std::f64::consts::PI;
+use modppl::prelude::*;
+
+use crate::types::*;
+use crate::linear::*;
+
+
+/* directions */
+
+/// how far from unit length a direction may be and still be on the sphere
+const UNIT_TOLERANCE: f32 = 1e-3;
+
+fn on_sphere(v: &Vec3) -> bool {
+    (vec3_norm(v) - 1.0).abs() <= UNIT_TOLERANCE
+}
+
+/// two unit vectors completing a unit vector `n` to a right-handed orthonormal basis
+pub fn tangent_frame(n: Vec3) -> (Vec3, Vec3) {
+    // Duff et al. (2017), without a branch on the largest component
+    let sign = 1f32.copysign(n[2]);
+    let a = -1.0 / (sign + n[2]);
+    let b = n[0] * n[1] * a;
+    ([1.0 + sign * n[0] * n[0] * a, sign * b, -sign * n[0]], [b, sign + n[1] * n[1] * a, -n[1]])
+}
+
+/// the direction at cosine `w` from a unit vector `n` and at angle `phi` about it
+fn direction_about(n: Vec3, w: f64, phi: f64) -> Vec3 {
+    let (t, b) = tangent_frame(n);
+    let r = (1. - w * w).max(0.).sqrt();
+    let (tc, bc) = ((r * phi.cos()) as f32, (r * phi.sin()) as f32);
+    [0, 1, 2].map(|i| w as f32 * n[i] + tc * t[i] + bc * b[i])
+}
+
+fn normalized(v: Vec3) -> Vec3 {
+    let mut v = v;
+    vec3_normalize(&mut v);
+    v
+}
+
+/// uniform distribution type on the unit sphere, with density w.r.t. surface area
+pub struct UniformS2 { }
+pub const uniform_s2: UniformS2 = UniformS2 { };
+
+impl Distribution<Vec3,()> for UniformS2 {
+    fn logpdf(&self, x: &Vec3, _: ()) -> f64 {
+        if on_sphere(x) { -(4. * PI).ln() } else { f64::NEG_INFINITY }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, _: ()) -> Vec3 {
+        let z = 2.0 * u01(rng) as f32 - 1.0;
+        let theta = 2.0 * std::f32::consts::PI * u01(rng) as f32;
+        let r = (1.0 - z * z).sqrt();
+        [r * theta.cos(), r * theta.sin(), z]
+    }
+}
+
+/// von Mises-Fisher distribution type on the unit sphere, with params `(mu, kappa)`: the density is proportional to
+/// `exp(kappa mu.x)` around the mean direction `mu`, and uniform when `kappa` is 0
+pub struct VonMisesFisher { }
+pub const von_mises_fisher: VonMisesFisher = VonMisesFisher { };
+
+impl Distribution<Vec3,(Vec3,f32)> for VonMisesFisher {
+    fn logpdf(&self, x: &Vec3, params: (Vec3,f32)) -> f64 {
+        let (mu, kappa) = (normalized(params.0), params.1 as f64);
+        if !on_sphere(x) {
+            return f64::NEG_INFINITY;
+        }
+        if kappa < 1e-6 {
+            return uniform_s2.logpdf(x, ());
+        }
+        // log(kappa / (4 pi sinh(kappa))), stable for large kappa
+        let log_normalizer = kappa.ln() - (2. * PI).ln() - kappa - (-(-2. * kappa).exp()).ln_1p();
+        log_normalizer + kappa * vec3_dot(&mu, x) as f64
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Vec3,f32)) -> Vec3 {
+        let (mu, kappa) = (normalized(params.0), params.1 as f64);
+        if kappa < 1e-6 {
+            return uniform_s2.random(rng, ());
+        }
+        // the cosine to the mean direction by inversion (Wood 1994), and a uniform angle about it
+        let u = u01(rng);
+        let w = 1. + (u + (1. - u) * (-2. * kappa).exp()).ln() / kappa;
+        direction_about(mu, w.clamp(-1., 1.), 2. * PI * u01(rng))
+    }
+}
+
+/// uniform distribution type on the hemisphere of unit directions around a param `axis`
+pub struct UniformHemisphere { }
+pub const uniform_hemisphere: UniformHemisphere = UniformHemisphere { };
+
+impl Distribution<Vec3,Vec3> for UniformHemisphere {
+    fn logpdf(&self, x: &Vec3, axis: Vec3) -> f64 {
+        if on_sphere(x) && vec3_dot(x, &axis) >= 0.0 { -(2. * PI).ln() } else { f64::NEG_INFINITY }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, axis: Vec3) -> Vec3 {
+        direction_about(normalized(axis), u01(rng), 2. * PI * u01(rng))
+    }
+}
+
+/// cosine-weighted (Lambertian) distribution type on the hemisphere of unit directions around a param `axis`: the
+/// density is `cos(theta) / pi` at angle `theta` from the axis
+pub struct CosineHemisphere { }
+pub const cosine_hemisphere: CosineHemisphere = CosineHemisphere { };
+
+impl Distribution<Vec3,Vec3> for CosineHemisphere {
+    fn logpdf(&self, x: &Vec3, axis: Vec3) -> f64 {
+        let cos_theta = vec3_dot(x, &normalized(axis)) as f64;
+        if on_sphere(x) && cos_theta >= 0.0 { cos_theta.ln() - PI.ln() } else { f64::NEG_INFINITY }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, axis: Vec3) -> Vec3 {
+        direction_about(normalized(axis), u01(rng).sqrt(), 2. * PI * u01(rng))
+    }
+}
+
+
+/* rotations */
+
+/// log of the modified Bessel function of the first kind `I_1`, for `x > 0` (Abramowitz & Stegun 9.8.3 and 9.8.4)
+fn log_bessel_i1(x: f64) -> f64 {
+    if x < 3.75 {
+        let t = (x / 3.75) * (x / 3.75);
+        x.ln() + (0.5 + t*(0.87890594 + t*(0.51498869 + t*(0.15084934 + t*(0.02658733 + t*(0.00301532
+            + t*0.00032411)))))).ln()
+    } else {
+        let t = 3.75 / x;
+        let series = 0.39894228 + t*(-0.03988024 + t*(-0.00362018 + t*(0.00163801 + t*(-0.01031555
+            + t*(0.02282967 + t*(-0.02895312 + t*(0.01787654 - t*0.00420059)))))));
+        x - 0.5 * x.ln() + series.ln()
+    }
+}
+
+/// `q` scaled to unit length
+fn normalized_quat(q: Quat) -> Quat {
+    vec4_scale(&q, 1.0 / quat_norm(&q))
+}
+
+/// the cosine of the angle between two quaternions on the 3-sphere, in double precision so that it stays accurate
+/// (and symmetric) for nearby rotations
+fn quat_cosine(p: &Quat, q: &Quat) -> f64 {
+    let dot = |a: &Quat, b: &Quat| (0..4).map(|i| a[i] as f64 * b[i] as f64).sum::<f64>();
+    dot(p, q) / (dot(p, p) * dot(q, q)).sqrt()
+}
+
+/// uniform distribution type on rotations, as unit quaternions with density w.r.t. the area of the unit 3-sphere
+pub struct UniformSO3 { }
+pub const uniform_so3: UniformSO3 = UniformSO3 { };
+
+impl Distribution<Quat,()> for UniformSO3 {
+    fn logpdf(&self, q: &Quat, _: ()) -> f64 {
+        if (quat_norm(q) - 1.0).abs() <= UNIT_TOLERANCE { -(2. * PI * PI).ln() } else { f64::NEG_INFINITY }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, _: ()) -> Quat {
+        // Shoemake (1992)
+        let (u1, u2, u3) = (u01(rng), 2. * PI * u01(rng), 2. * PI * u01(rng));
+        let (a, b) = ((1. - u1).sqrt(), u1.sqrt());
+        [a * u2.sin(), a * u2.cos(), b * u3.sin(), b * u3.cos()].map(|v| v as f32)
+    }
+}
+
+/// isotropic distribution type on rotations around a mean rotation, with params `(mean, kappa)`: the density on unit
+/// quaternions is proportional to `cosh(kappa mean.q)`, a von Mises-Fisher distribution on the 3-sphere made
+/// symmetric so that `q` and `-q`, the same rotation, are equally likely. For large `kappa`, the rotation vector
+/// from the mean is about Gaussian with standard deviation `2 / sqrt(kappa)` in every direction.
+pub struct IsotropicSO3 { }
+pub const isotropic_so3: IsotropicSO3 = IsotropicSO3 { };
+
+impl IsotropicSO3 {
+    /// the concentration whose rotation vectors have standard deviation `stdev` in every direction
+    pub fn concentration(stdev: f32) -> f32 {
+        4.0 / (stdev * stdev)
+    }
+}
+
+impl Distribution<Quat,(Quat,f32)> for IsotropicSO3 {
+    fn logpdf(&self, q: &Quat, params: (Quat,f32)) -> f64 {
+        let kappa = params.1 as f64;
+        if kappa < 1e-6 {
+            return uniform_so3.logpdf(q, ());
+        }
+        if (quat_norm(q) - 1.0).abs() > UNIT_TOLERANCE {
+            return f64::NEG_INFINITY;
+        }
+        // log(kappa / (4 pi^2 I_1(kappa))) of the von Mises-Fisher distribution, and the log of cosh
+        let log_normalizer = kappa.ln() - (4. * PI * PI).ln() - log_bessel_i1(kappa);
+        let t = kappa * quat_cosine(&params.0, q).abs();
+        log_normalizer + t + (-2. * t).exp().ln_1p() - 2f64.ln()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Quat,f32)) -> Quat {
+        let (mean, kappa) = (normalized_quat(params.0), params.1 as f64);
+        if kappa < 1e-6 {
+            return uniform_so3.random(rng, ());
+        }
+        // the cosine `w` to the mean, whose density is proportional to `exp(kappa w) sqrt(1 - w^2)`, by rejection,
+        // and the sine `r`
+        let (w, r) = if kappa < 10. {
+            // Wood (1994), on the 3-sphere
+            let b = (-2. * kappa + (4. * kappa * kappa + 9.).sqrt()) / 3.;
+            let x0 = (1. - b) / (1. + b);
+            let c = kappa * x0 + 3. * (1. - x0 * x0).ln();
+            loop {
+                let z = beta.random(rng, (1.5, 1.5));
+                let w = (1. - (1. + b) * z) / (1. - (1. - b) * z);
+                if kappa * w + 3. * (1. - x0 * w).ln() - c >= u01(rng).ln() {
+                    break (w, (1. - w * w).max(0.).sqrt());
+                }
+            }
+        } else {
+            // where that loses precision, `1 - w` from a Gamma(3/2, kappa) envelope
+            loop {
+                let z = normal.random(rng, (0., 1.));
+                let s = (0.5 * z * z - u01(rng).ln()) / kappa;
+                let u = u01(rng);
+                if s <= 2. && u * u <= 1. - 0.5 * s {
+                    break (1. - s, (s * (2. - s)).sqrt());
+                }
+            }
+        };
+        // and a uniform direction about it, as a rotation relative to the mean
+        let v = vec3_scale(&uniform_s2.random(rng, ()), r as f32);
+        let q = quat_mul(mean, [v[0], v[1], v[2], w as f32]);
+        if u01(rng) < 0.5 { q } else { vec4_scale(&q, -1.0) }
+    }
+}
+
+
+/* poses */
+
+fn pose(p: Vec3, q: Quat) -> Pose {
+    [p[0], p[1], p[2], q[0], q[1], q[2], q[3]]
+}
+
+/// Gaussian distribution type on poses around a mean pose, with params `(mean, sigma, kappa)`: the position is
+/// isotropic Gaussian around the mean position with standard deviation `sigma`, and the orientation is
+/// `isotropic_so3` around the mean orientation with concentration `kappa`
+pub struct GaussianPose { }
+pub const gaussian_pose: GaussianPose = GaussianPose { };
+
+impl Distribution<Pose,(Pose,f32,f32)> for GaussianPose {
+    fn logpdf(&self, x: &Pose, params: (Pose,f32,f32)) -> f64 {
+        let (mean, sigma, kappa) = params;
+        let w_position: f64 = (0..=2).map(|i| normal.logpdf(&(x[i] as f64), (mean[i] as f64, sigma as f64))).sum();
+        w_position + isotropic_so3.logpdf(&pose_to_quat(*x), (pose_to_quat(mean), kappa))
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Pose,f32,f32)) -> Pose {
+        let (mean, sigma, kappa) = params;
+        let p = [0, 1, 2].map(|i| normal.random(rng, (mean[i] as f64, sigma as f64)) as f32);
+        pose(p, isotropic_so3.random(rng, (pose_to_quat(mean), kappa)))
+    }
+}
+
+/// uniform distribution type on poses, with params `(lo, hi)`: the position is uniform in the box `[lo, hi]` and
+/// the orientation is `uniform_so3`
+pub struct UniformPose { }
+pub const uniform_pose: UniformPose = UniformPose { };
+
+impl Distribution<Pose,(Vec3,Vec3)> for UniformPose {
+    fn logpdf(&self, x: &Pose, params: (Vec3,Vec3)) -> f64 {
+        let (lo, hi) = params;
+        let w_position: f64 = (0..=2).map(|i| uniform.logpdf(&(x[i] as f64), (lo[i] as f64, hi[i] as f64))).sum();
+        w_position + uniform_so3.logpdf(&pose_to_quat(*x), ())
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Vec3,Vec3)) -> Pose {
+        let (lo, hi) = params;
+        let p = [0, 1, 2].map(|i| uniform.random(rng, (lo[i] as f64, hi[i] as f64)) as f32);
+        pose(p, uniform_so3.random(rng, ()))
+    }
+}
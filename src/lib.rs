@@ -2,6 +2,8 @@ pub mod types;
 pub mod linear;
 
 pub mod config;
+pub mod color;
+pub mod geometry;
 pub mod ray;
 pub mod models;
 pub mod serialization;
@@ -11,6 +13,8 @@ pub use types::*;
 pub use linear::*;
 
 pub use config::*;
+pub use color::*;
+pub use geometry::*;
 pub use ray::*;
 pub use models::*;
 pub use serialization::*;
\ No newline at end of file
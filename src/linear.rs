@@ -55,6 +55,18 @@ pub fn unproject_inv(pos: Vec3, inv_m: Mat4, vp: Vec4) -> Vec3 {
     dest
 }
 
+#[inline]
+pub fn project(pos: Vec3, m: Mat4, vp: Vec4) -> Vec3 {
+    let v = mat4_mulv(m, [pos[0], pos[1], pos[2], 1.0]);
+    let v = vec4_scale(&v, 1.0 / v[3]);
+
+    let mut dest = vec3_zero();
+    dest[0] = 0.5 * (v[0] + 1.0) * vp[2] + vp[0];
+    dest[1] = 0.5 * (v[1] + 1.0) * vp[3] + vp[1];
+    dest[2] = v[2];
+    dest
+}
+
 #[inline]
 pub fn ray_at(ray_origin: Vec3, ray_dir: Vec3, distance: f32) -> Vec3 {
     vec3_add(ray_origin, vec3_scale(&ray_dir, distance))
@@ -76,6 +88,25 @@ pub fn perspective(fovy: f32, aspect_ratio: f32, nearz: f32, farz: f32) -> Mat4
     out
 }
 
+#[inline]
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, nearz: f32, farz: f32) -> Mat4 {
+    let mut out = mat4_zero();
+
+    let rl = 1.0 / (right - left);
+    let tb = 1.0 / (top - bottom);
+    let fnorm = -1.0 / (farz - nearz);
+
+    out[0][0] = 2.0 * rl;
+    out[1][1] = 2.0 * tb;
+    out[2][2] = 2.0 * fnorm;
+    out[3][0] = -(right + left) * rl;
+    out[3][1] = -(top + bottom) * tb;
+    out[3][2] = (farz + nearz) * fnorm;
+    out[3][3] = 1.0;
+
+    out
+}
+
 #[inline]
 pub fn euler_xyz(angles: Vec3) -> Quat {
     let sx   = angles[0].sin(); let cx = angles[0].cos();
@@ -208,6 +239,36 @@ pub fn pose_to_mat4(p: Pose) -> Mat4 {
     out
 }
 
+#[inline]
+pub fn quat_mul(p: Quat, q: Quat) -> Quat {
+    [
+        p[3] * q[0] + p[0] * q[3] + p[1] * q[2] - p[2] * q[1],
+        p[3] * q[1] - p[0] * q[2] + p[1] * q[3] + p[2] * q[0],
+        p[3] * q[2] + p[0] * q[1] - p[1] * q[0] + p[2] * q[3],
+        p[3] * q[3] - p[0] * q[0] - p[1] * q[1] - p[2] * q[2]
+    ]
+}
+
+#[inline]
+pub fn quat_rotatev(q: Quat, v: Vec3) -> Vec3 {
+    let p = vec4_scale(&q, 1.0 / quat_norm(&q));
+    let u = [p[0], p[1], p[2]];
+    let s = p[3];
+
+    let v1 = vec3_scale(&u, 2.0 * vec3_dot(&u, &v));
+    let v2 = vec3_scale(&v, s * s - vec3_dot(&u, &u));
+    let v3 = vec3_scale(&vec3_cross(&u, &v), 2.0 * s);
+    vec3_add(vec3_add(v1, v2), v3)
+}
+
+#[inline]
+pub fn pose_mul(a: Pose, b: Pose) -> Pose {
+    let qa = pose_to_quat(a);
+    let p = vec3_add(pose_to_vec3(a), quat_rotatev(qa, pose_to_vec3(b)));
+    let q = quat_mul(qa, pose_to_quat(b));
+    [p[0], p[1], p[2], q[0], q[1], q[2], q[3]]
+}
+
 #[inline]
 pub fn vec3_add(a: Vec3, b: Vec3) -> Vec3 {
     [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
@@ -223,6 +284,11 @@ pub fn vec3_dot(a: &Vec3, b: &Vec3) -> f32 {
     a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
 }
 
+#[inline]
+pub fn vec3_cross(a: &Vec3, b: &Vec3) -> Vec3 {
+    [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
 #[inline]
 pub fn vec3_scale(v: &Vec3, a: f32) -> Vec3 {
     [a*v[0], a*v[1], a*v[2]]
@@ -266,6 +332,38 @@ pub fn quat_norm(q: &Quat) -> f32 {
     vec4_norm(q)
 }
 
+#[inline]
+pub fn quat_dot(p: &Quat, q: &Quat) -> f32 {
+    p[0]*q[0] + p[1]*q[1] + p[2]*q[2] + p[3]*q[3]
+}
+
+#[inline]
+pub fn quat_conj(q: Quat) -> Quat {
+    [-q[0], -q[1], -q[2], q[3]]
+}
+
+#[inline]
+pub fn quat_from_rotvec(v: Vec3) -> Quat {
+    let angle = vec3_norm(&v);
+    if angle < 1e-6 {
+        let q = [0.5 * v[0], 0.5 * v[1], 0.5 * v[2], 1.0];
+        return vec4_scale(&q, 1.0 / quat_norm(&q));
+    }
+    let s = (0.5 * angle).sin() / angle;
+    [s * v[0], s * v[1], s * v[2], (0.5 * angle).cos()]
+}
+
+#[inline]
+pub fn quat_to_rotvec(q: Quat) -> Vec3 {
+    let q = if q[3] < 0.0 { vec4_scale(&q, -1.0) } else { q };
+    let u = [q[0], q[1], q[2]];
+    let s = vec3_norm(&u);
+    if s < 1e-6 {
+        return vec3_scale(&u, 2.0 / q[3]);
+    }
+    vec3_scale(&u, 2.0 * s.atan2(q[3]) / s)
+}
+
 #[inline]
 pub fn mat4_scale_p(mat: &mut Mat4, v: f32) {
     mat[0][0] *= v; mat[0][1] *= v; mat[0][2] *= v; mat[0][3] *= v;
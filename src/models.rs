@@ -1,11 +1,18 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::thread;
 use std::f32::consts::PI;
-use float_extras::f64::erf;
+use std::f64::consts::{PI as PI64, SQRT_2};
+use float_extras::f64::{erfc, lgamma};
 use modppl::prelude::*;
 
 use crate::types::*;
 use crate::linear::*;
 use crate::config::*;
 use crate::ray::*;
+use crate::color::*;
+use crate::geometry::*;
 
 
 /* pixel likelihoods */
@@ -14,119 +21,1514 @@ use crate::ray::*;
 pub struct TruncatedNormal { }
 pub const truncated_normal: TruncatedNormal = TruncatedNormal { };
 
-fn normal_cdf(x: &f64, params: (f64,f64)) -> f64 {
-    let (mu, sigma) = params;
-    let xi = (x - mu) / sigma;
-    0.5*(1. + erf(xi as f64/2f64.sqrt()))
+/// log of the standard normal CDF, accurate far into both tails
+fn log_normal_cdf(x: f64) -> f64 {
+    if x > 0. {
+        (-0.5 * erfc(x / SQRT_2)).ln_1p()
+    } else if x > -20. {
+        (0.5 * erfc(-x / SQRT_2)).ln()
+    } else {
+        // asymptotic expansion of the Mills ratio, once erfc would underflow
+        let x2 = x * x;
+        let series = 1. - 1. / x2 + 3. / (x2 * x2) - 15. / (x2 * x2 * x2);
+        -0.5 * x2 - (-x).ln() - 0.5 * (2. * PI64).ln() + series.ln()
+    }
+}
+
+/// log of the probability mass in `[lo, hi]` of a distribution symmetric about zero with log CDF `log_cdf`
+fn log_symmetric_mass(log_cdf: impl Fn(f64) -> f64, lo: f64, hi: f64) -> f64 {
+    if lo > 0. {
+        // both bounds in the upper tail: reflect into the lower tail where the CDF doesn't round to one
+        return log_symmetric_mass(log_cdf, -hi, -lo);
+    }
+    let (l_lo, l_hi) = (log_cdf(lo), log_cdf(hi));
+    l_hi + (-(l_lo - l_hi).exp_m1()).ln()
+}
+
+/// exactly samples a standard normal truncated to `[lo, hi]` by rejection, choosing between
+/// normal, uniform and exponential proposals (Robert, 1995) so the acceptance rate is bounded below
+fn standard_truncated_normal(rng: &mut ThreadRng, lo: f64, hi: f64) -> f64 {
+    if hi <= 0. {
+        return -standard_truncated_normal(rng, -hi, -lo);
+    }
+    if lo <= 0. && hi - lo >= (2. * PI64).sqrt() {
+        loop {
+            let z = normal.random(rng, (0., 1.));
+            if lo <= z && z <= hi { return z; }
+        }
+    } else if lo <= 0. {
+        loop {
+            let z = lo + (hi - lo) * u01(rng);
+            if u01(rng) <= (-0.5 * z * z).exp() { return z; }
+        }
+    } else if (hi - lo) * (lo + hi) <= 2. {
+        loop {
+            let z = lo + (hi - lo) * u01(rng);
+            if u01(rng) <= (0.5 * (lo * lo - z * z)).exp() { return z; }
+        }
+    } else {
+        let lambda = 0.5 * (lo + (lo * lo + 4.).sqrt());
+        loop {
+            let z = lo - (1. - u01(rng)).ln() / lambda;
+            if z <= hi && u01(rng) <= (-0.5 * (z - lambda) * (z - lambda)).exp() { return z; }
+        }
+    }
+}
+
+impl Distribution<f64,(f64,f64,f64,f64)> for TruncatedNormal {
+    fn logpdf(&self, x: &f64, params: (f64,f64,f64,f64)) -> f64 {
+        let (mu, sigma, a, b) = params;
+        if a <= *x && *x <= b {
+            normal.logpdf(x, (mu, sigma)) - log_symmetric_mass(log_normal_cdf, (a - mu) / sigma, (b - mu) / sigma)
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f64,f64,f64,f64)) -> f64 {
+        let (mu, sigma, a, b) = params;
+        if sigma <= 0. {
+            return mu.clamp(a, b);
+        }
+        let z = standard_truncated_normal(rng, (a - mu) / sigma, (b - mu) / sigma);
+        (mu + sigma * z).clamp(a, b)
+    }
 }
 
 impl Distribution<f32,(f32,f32,f32,f32)> for TruncatedNormal {
     fn logpdf(&self, x: &f32, params: (f32,f32,f32,f32)) -> f64 {
         let (mu, sigma, a, b) = params;
+        self.logpdf(&(*x as f64), (mu as f64, sigma as f64, a as f64, b as f64))
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32,f32)) -> f32 {
+        let (mu, sigma, a, b) = params;
+        (self.random(rng, (mu as f64, sigma as f64, a as f64, b as f64)) as f32).clamp(a, b)
+    }
+}
+
+/// truncated Laplace distribution type
+pub struct TruncatedLaplace { }
+pub const truncated_laplace: TruncatedLaplace = TruncatedLaplace { };
+
+/// log of the standard Laplace CDF
+fn log_laplace_cdf(x: f64) -> f64 {
+    if x < 0. { x - 2f64.ln() } else { (-0.5 * (-x).exp()).ln_1p() }
+}
+
+/// exactly samples a standard Laplace truncated to `[lo, hi]` by inverting its CDF
+fn standard_truncated_laplace(rng: &mut ThreadRng, lo: f64, hi: f64) -> f64 {
+    if lo > 0. {
+        return -standard_truncated_laplace(rng, -hi, -lo);
+    }
+    if hi <= 0. {
+        // the density is proportional to exp(z) on the interval
+        return hi + (-u01(rng) * -(lo - hi).exp_m1()).ln_1p();
+    }
+    let (p_lo, p_hi) = (0.5 * lo.exp(), 1. - 0.5 * (-hi).exp());
+    let p = p_lo + (p_hi - p_lo) * u01(rng);
+    if p < 0.5 { (2. * p).ln() } else { -(2. * (1. - p)).ln() }
+}
+
+impl Distribution<f32,(f32,f32,f32,f32)> for TruncatedLaplace {
+    fn logpdf(&self, x: &f32, params: (f32,f32,f32,f32)) -> f64 {
+        let (mu, scale, a, b) = params;
+        if a <= *x && *x <= b {
+            let (mu, scale) = (mu as f64, scale as f64);
+            laplace.logpdf(&(*x as f64), (mu, scale))
+              - log_symmetric_mass(log_laplace_cdf, (a as f64 - mu) / scale, (b as f64 - mu) / scale)
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32,f32)) -> f32 {
+        let (mu, scale, a, b) = params;
+        if scale <= 0. {
+            return mu.clamp(a, b);
+        }
+        let (mu, scale) = (mu as f64, scale as f64);
+        let z = standard_truncated_laplace(rng, (a as f64 - mu) / scale, (b as f64 - mu) / scale);
+        ((mu + scale * z) as f32).clamp(a, b)
+    }
+}
+
+/// truncated Student-t distribution type, with params `(mu, scale, nu, a, b)`
+pub struct TruncatedStudentT { }
+pub const truncated_student_t: TruncatedStudentT = TruncatedStudentT { };
+
+/// continued fraction of the regularized incomplete beta function (Numerical Recipes `betacf`)
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    let nonzero = |v: f64| if v.abs() < 1e-300 { 1e-300 } else { v };
+    let (qab, qap, qam) = (a + b, a + 1., a - 1.);
+    let mut c = 1.;
+    let mut d = 1. / nonzero(1. - qab * x / qap);
+    let mut h = d;
+    for m in 1..300 {
+        let (m, m2) = (m as f64, 2. * m as f64);
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1. / nonzero(1. + aa * d);
+        c = nonzero(1. + aa / c);
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1. / nonzero(1. + aa * d);
+        c = nonzero(1. + aa / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// log of the standard Student-t CDF with `nu` degrees of freedom
+fn log_student_t_cdf(t: f64, nu: f64) -> f64 {
+    // P(T < -|t|) = I_x(nu/2, 1/2) / 2 with x = nu/(nu + t^2), and y = 1 - x computed without cancellation
+    let (a, b) = (0.5 * nu, 0.5);
+    let (x, y) = (nu / (nu + t * t), t * t / (nu + t * t));
+    let log_beta = lgamma(a) + lgamma(b) - lgamma(a + b);
+    let log_front = a * x.ln() + b * y.ln() - log_beta;
+    let log_tail = if x < (a + 1.) / (a + b + 2.) {
+        log_front - a.ln() + beta_cf(a, b, x).ln()
+    } else {
+        (-(log_front - b.ln()).exp() * beta_cf(b, a, y)).ln_1p()
+    } - 2f64.ln();
+    if t < 0. { log_tail } else { (-log_tail.exp()).ln_1p() }
+}
+
+/// log density of the (untruncated) Student-t with location `mu`, scale `scale` and `nu` degrees of freedom
+fn log_student_t_density(x: f64, mu: f64, scale: f64, nu: f64) -> f64 {
+    let z = (x - mu) / scale;
+    lgamma(0.5 * (nu + 1.)) - lgamma(0.5 * nu) - 0.5 * (nu * PI64).ln() - scale.ln() - 0.5 * (nu + 1.) * (z * z / nu).ln_1p()
+}
+
+/// exactly samples a standard Student-t truncated to `[lo, hi]` by inverting its CDF with bisection
+fn standard_truncated_student_t(rng: &mut ThreadRng, nu: f64, lo: f64, hi: f64) -> f64 {
+    if lo > 0. {
+        return -standard_truncated_student_t(rng, nu, -hi, -lo);
+    }
+    // log of the target CDF value, interpolating between the bounds in log space
+    let u = u01(rng);
+    let (l_lo, l_hi) = (log_student_t_cdf(lo, nu) + (1. - u).ln(), log_student_t_cdf(hi, nu) + u.ln());
+    let l_max = l_lo.max(l_hi);
+    let target = l_max + ((l_lo - l_max).exp() + (l_hi - l_max).exp()).ln();
+    let (mut lo, mut hi) = (lo, hi);
+    for _ in 0..64 {
+        let mid = 0.5 * (lo + hi);
+        if log_student_t_cdf(mid, nu) < target { lo = mid; } else { hi = mid; }
+    }
+    0.5 * (lo + hi)
+}
+
+impl Distribution<f32,(f32,f32,f32,f32,f32)> for TruncatedStudentT {
+    fn logpdf(&self, x: &f32, params: (f32,f32,f32,f32,f32)) -> f64 {
+        let (mu, scale, nu, a, b) = params;
         if a <= *x && *x <= b {
-            normal.logpdf(&(*x as f64), (mu as f64, sigma as f64)) 
-              - (normal_cdf(&(b as f64), (mu as f64, sigma as f64)) -
-                 normal_cdf(&(a as f64), (mu as f64, sigma as f64))).ln()
-              - ((sigma as f64).ln())
+            let (mu, scale, nu) = (mu as f64, scale as f64, nu as f64);
+            log_student_t_density(*x as f64, mu, scale, nu)
+              - log_symmetric_mass(|t| log_student_t_cdf(t, nu), (a as f64 - mu) / scale, (b as f64 - mu) / scale)
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32,f32,f32)) -> f32 {
+        let (mu, scale, nu, a, b) = params;
+        if scale <= 0. {
+            return mu.clamp(a, b);
+        }
+        let (mu, scale) = (mu as f64, scale as f64);
+        let z = standard_truncated_student_t(rng, nu as f64, (a as f64 - mu) / scale, (b as f64 - mu) / scale);
+        ((mu + scale * z) as f32).clamp(a, b)
+    }
+}
+
+/// shape of the inlier pixel noise: Gaussian, or heavier-tailed Laplace or Student-t with `nu` degrees of freedom
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PixelNoise {
+    #[default]
+    Gaussian,
+    Laplace,
+    StudentT(f32)
+}
+
+/// noisy pixel distribution type: with probability `outlier_prob` a uniform outlier
+/// on [0, 1], and otherwise truncated `noise` of scale `sigma` around the true value
+pub struct NoisyPixel { pub noise: PixelNoise }
+pub const noisy_pixel: NoisyPixel = NoisyPixel { noise: PixelNoise::Gaussian };
+
+impl NoisyPixel {
+    fn inlier_random(&self, rng: &mut ThreadRng, mu: f32, sigma: f32) -> f32 {
+        match self.noise {
+            PixelNoise::Gaussian => truncated_normal.random(rng, (mu, sigma, 0.0, 1.0)),
+            PixelNoise::Laplace => truncated_laplace.random(rng, (mu, sigma, 0.0, 1.0)),
+            PixelNoise::StudentT(nu) => truncated_student_t.random(rng, (mu, sigma, nu, 0.0, 1.0))
+        }
+    }
+
+    /// log of the mass on [0, 1] of the untruncated inlier noise, which normalizes its density
+    fn inlier_log_mass(&self, mu: f32, sigma: f32) -> f64 {
+        let (mu, sigma) = (mu as f64, sigma as f64);
+        let (lo, hi) = (-mu / sigma, (1. - mu) / sigma);
+        match self.noise {
+            PixelNoise::Gaussian => log_symmetric_mass(log_normal_cdf, lo, hi),
+            PixelNoise::Laplace => log_symmetric_mass(log_laplace_cdf, lo, hi),
+            PixelNoise::StudentT(nu) => log_symmetric_mass(|t| log_student_t_cdf(t, nu as f64), lo, hi)
+        }
+    }
+
+    /// `logpdf` given the `inlier_log_mass` of the params
+    fn logpdf_with_mass(&self, x: &f32, params: (f32,f32,f32), log_mass: f64) -> f64 {
+        let (mu, outlier_prob, sigma) = params;
+        if !(0.0 <= *x && *x <= 1.0) {
+            return f64::NEG_INFINITY;
+        }
+        let (x, mu_64, sigma_64) = (*x as f64, mu as f64, sigma as f64);
+        let w_density = match self.noise {
+            PixelNoise::Gaussian => normal.logpdf(&x, (mu_64, sigma_64)),
+            PixelNoise::Laplace => laplace.logpdf(&x, (mu_64, sigma_64)),
+            PixelNoise::StudentT(nu) => log_student_t_density(x, mu_64, sigma_64, nu as f64)
+        };
+        let w_outlier = (outlier_prob as f64).ln();
+        let w_inlier = (1. - outlier_prob as f64).ln() + w_density - log_mass;
+        let w_max = w_outlier.max(w_inlier);
+        if w_max == f64::NEG_INFINITY {
+            return w_max;
+        }
+        w_max + ((w_outlier - w_max).exp() + (w_inlier - w_max).exp()).ln()
+    }
+
+    /// `logpdf`, looking the normalizer up in (or adding it to) `cache`
+    fn cached_logpdf(&self, x: &f32, params: (f32,f32,f32), cache: &mut LogMassCache) -> f64 {
+        let (mu, _, sigma) = params;
+        let log_mass = *cache.entry((mu.to_bits(), sigma.to_bits())).or_insert_with(|| self.inlier_log_mass(mu, sigma));
+        self.logpdf_with_mass(x, params, log_mass)
+    }
+}
+
+impl Distribution<f32,(f32,f32,f32)> for NoisyPixel {
+    fn logpdf(&self, x: &f32, params: (f32,f32,f32)) -> f64 {
+        let (mu, _, sigma) = params;
+        self.logpdf_with_mass(x, params, self.inlier_log_mass(mu, sigma))
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32)) -> f32 {
+        let (mu, outlier_prob, sigma) = params;
+        if u01(rng) < outlier_prob as f64 {
+            u01(rng) as f32
+        } else {
+            self.inlier_random(rng, mu, sigma)
+        }
+    }
+}
+
+impl NoisyPixel {
+    /// posterior probability that `x` was drawn from the outlier component
+    pub fn outlier_posterior(&self, x: &f32, params: (f32,f32,f32)) -> f64 {
+        let (_, outlier_prob, _) = params;
+        if outlier_prob <= 0. {
+            return 0.;
+        }
+        ((outlier_prob as f64).ln() - self.logpdf(x, params)).exp().min(1.)
+    }
+}
+
+/// inlier normalizers of `NoisyPixel` by the bits of `(mu, sigma)`, which repeat across flat regions of a render
+type LogMassCache = HashMap<(u32, u32), f64>;
+
+/// most threads that split an image likelihood, and fewest pixels worth spawning one for
+const MAX_THREADS: usize = 8;
+const MIN_THREAD_PIXELS: usize = 4096;
+
+/// evaluates `pixel_logpdf` at each of `n` pixel indices, splitting them among as many threads as are worth it, up
+/// to `MAX_THREADS`, that each keep a normalizer cache
+fn pixel_log_likelihoods(n: usize, pixel_logpdf: impl Fn(usize, &mut LogMassCache) -> f64 + Sync) -> Vec<f64> {
+    static THREADS: OnceLock<usize> = OnceLock::new();
+    let threads = *THREADS.get_or_init(|| thread::available_parallelism().map_or(1, |p| p.get()).min(MAX_THREADS));
+    let threads = threads.min(n / MIN_THREAD_PIXELS).max(1);
+    let mut map = vec![0.; n];
+    if threads == 1 {
+        let mut cache = LogMassCache::new();
+        map.iter_mut().enumerate().for_each(|(i, w)| *w = pixel_logpdf(i, &mut cache));
+        return map;
+    }
+    let chunk = n.div_ceil(threads);
+    thread::scope(|scope| {
+        for (t, part) in map.chunks_mut(chunk).enumerate() {
+            let pixel_logpdf = &pixel_logpdf;
+            scope.spawn(move || {
+                let mut cache = LogMassCache::new();
+                part.iter_mut().enumerate().for_each(|(i, w)| *w = pixel_logpdf(t*chunk + i, &mut cache));
+            });
+        }
+    });
+    map
+}
+
+/// noisy depth distribution type
+pub struct NoisyDepths { pub noise: PixelNoise, pub mask: Mask }
+pub const noisy_depths: NoisyDepths = NoisyDepths { noise: PixelNoise::Gaussian, mask: Mask::All };
+
+impl Distribution<Depths,(Depths,f32,f32)> for NoisyDepths {
+    fn logpdf(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> f64 {
+        self.log_likelihood_map(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Depths,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let mut noisy_pixels = vec![];
+        for y in 0..H {
+            for x in 0..W {
+                noisy_pixels.push(pixel.random(rng, (pixels[y*W + x], outlier_prob, noise)));
+            }
+        }
+        noisy_pixels
+    }
+}
+
+impl NoisyDepths {
+    /// per-pixel log-likelihoods of the observed depths, zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> Vec<f64> {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        pixel_log_likelihoods(AREA, |i, cache| {
+            if !self.mask.contains(i % W, i / W) { return 0.; }
+            pixel.cached_logpdf(&noisy_pixels[i], (pixels[i], outlier_prob, noise), cache)
+        })
+    }
+
+    /// per-pixel posterior probabilities that the observed depth is an outlier
+    pub fn outlier_posteriors(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        noisy_pixels.iter().zip(pixels.iter())
+            .map(|(noisy_p, true_p)| pixel.outlier_posterior(noisy_p, (*true_p, outlier_prob, noise)) as f32)
+            .collect()
+    }
+}
+
+/// noisy (isotropic) color distribution type
+pub struct NoisyColors { pub noise: PixelNoise, pub mask: Mask }
+pub const noisy_colors: NoisyColors = NoisyColors { noise: PixelNoise::Gaussian, mask: Mask::All };
+
+impl Distribution<Colors,(Colors,f32,f32)> for NoisyColors {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> f64 {
+        self.log_likelihood_map(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,f32,f32)) -> Colors {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let mut noisy_pixels = vec![];
+        for y in 0..H {
+            for x in 0..W {
+                let mut noisy_p = vec3_zero();
+                for i in 0..=2 {
+                    noisy_p[i] = pixel.random(rng, (pixels[y*W + x][i], outlier_prob, noise));
+                }
+                noisy_pixels.push(noisy_p)
+            }
+        }
+        noisy_pixels
+    }
+}
+
+impl NoisyColors {
+    /// per-pixel log-likelihoods of the observed colors (summed over channels), zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Vec<f64> {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        pixel_log_likelihoods(AREA, |i, cache| {
+            if !self.mask.contains(i % W, i / W) { return 0.; }
+            (0..=2).map(|c| pixel.cached_logpdf(&noisy_pixels[i][c], (pixels[i][c], outlier_prob, noise), cache)).sum()
+        })
+    }
+
+    /// per-pixel posterior probabilities that any channel of the observed color is an outlier
+    pub fn outlier_posteriors(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        noisy_pixels.iter().zip(pixels.iter())
+            .map(|(noisy_p, true_p)| {
+                let inlier_prob = (0..=2)
+                    .map(|i| 1. - pixel.outlier_posterior(&noisy_p[i], (true_p[i], outlier_prob, noise)))
+                    .product::<f64>();
+                (1. - inlier_prob) as f32
+            })
+            .collect()
+    }
+}
+
+/// perceptual color distribution type, with params `(pixels, outlier_prob, lightness_sigma, chroma_sigma)`: scores
+/// colors in the lightness and chroma coordinates of `space`, each with probability `outlier_prob` a uniform outlier
+/// and otherwise truncated Gaussian noise on the `ranges` of the space, so that shading changes can be penalized less
+/// than hue changes
+pub struct PerceptualColors { pub space: ColorSpace, pub mask: Mask }
+
+impl PerceptualColors {
+    fn coord_logpdf(x: f32, mu: f32, outlier_prob: f32, sigma: f32, (lo, hi): (f32, f32)) -> f64 {
+        // the ranges bound the coordinates of all colors up to rounding
+        let x = x.clamp(lo, hi);
+        let w_outlier = (outlier_prob as f64).ln() - ((hi - lo) as f64).ln();
+        let w_inlier = (1. - outlier_prob as f64).ln() + truncated_normal.logpdf(&x, (mu.clamp(lo, hi), sigma, lo, hi));
+        let w_max = w_outlier.max(w_inlier);
+        if w_max == f64::NEG_INFINITY {
+            return w_max;
+        }
+        w_max + ((w_outlier - w_max).exp() + (w_inlier - w_max).exp()).ln()
+    }
+
+    /// per-pixel log-likelihoods of the observed colors, zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Colors, params: (Colors,f32,f32,f32)) -> Vec<f64> {
+        let (pixels, outlier_prob, lightness_sigma, chroma_sigma) = params;
+        let (sigmas, ranges) = ([lightness_sigma, chroma_sigma, chroma_sigma], self.space.ranges());
+        pixel_log_likelihoods(AREA, |i, _| {
+            if !self.mask.contains(i % W, i / W) { return 0.; }
+            let (noisy_p, true_p) = (self.space.coords(noisy_pixels[i]), self.space.coords(pixels[i]));
+            (0..=2).map(|c| Self::coord_logpdf(noisy_p[c], true_p[c], outlier_prob, sigmas[c], ranges[c])).sum()
+        })
+    }
+
+    /// per-pixel posterior probabilities that any coordinate of the observed color is an outlier, at full resolution
+    pub fn outlier_posteriors(&self, noisy_pixels: &Colors, params: (Colors,f32,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, lightness_sigma, chroma_sigma) = params;
+        if outlier_prob <= 0. {
+            return vec![0.0; noisy_pixels.len()];
+        }
+        let (sigmas, ranges) = ([lightness_sigma, chroma_sigma, chroma_sigma], self.space.ranges());
+        noisy_pixels.iter().zip(pixels.iter())
+            .map(|(noisy_p, true_p)| {
+                let (noisy_p, true_p) = (self.space.coords(*noisy_p), self.space.coords(*true_p));
+                let inlier_prob = (0..=2)
+                    .map(|i| {
+                        let range = ranges[i];
+                        let w = Self::coord_logpdf(noisy_p[i], true_p[i], outlier_prob, sigmas[i], range);
+                        let w_outlier = (outlier_prob as f64).ln() - ((range.1 - range.0) as f64).ln();
+                        1. - (w_outlier - w).exp().min(1.)
+                    })
+                    .product::<f64>();
+                (1. - inlier_prob) as f32
+            })
+            .collect()
+    }
+}
+
+impl Distribution<Colors,(Colors,f32,f32,f32)> for PerceptualColors {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,f32,f32,f32)) -> f64 {
+        self.log_likelihood_map(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,f32,f32,f32)) -> Colors {
+        let (pixels, outlier_prob, lightness_sigma, chroma_sigma) = params;
+        let (sigmas, ranges) = ([lightness_sigma, chroma_sigma, chroma_sigma], self.space.ranges());
+        pixels.iter()
+            .map(|p| {
+                let mut coords = self.space.coords(*p);
+                for i in 0..=2 {
+                    let (lo, hi) = ranges[i];
+                    coords[i] = if u01(rng) < outlier_prob as f64 {
+                        lo + (hi - lo) * u01(rng) as f32
+                    } else {
+                        truncated_normal.random(rng, (coords[i].clamp(lo, hi), sigmas[i], lo, hi))
+                    };
+                }
+                self.space.color(coords)
+            })
+            .collect()
+    }
+}
+
+
+/// a clean image of the static background seen by a fixed camera, with its own pixel noise
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackgroundPlate {
+    pub colors: Arc<Colors>,
+    pub outlier_prob: f32,
+    pub sigma: f32
+}
+
+impl BackgroundPlate {
+    pub fn new(colors: Colors) -> Self {
+        BackgroundPlate { colors: Arc::new(colors), outlier_prob: OUTLIER_PROB, sigma: PIXEL_NOISE }
+    }
+}
+
+/// background-subtraction color distribution type, with params `(pixels, foreground, outlier_prob, sigma)`: pixels
+/// in `foreground` are noisy around the render like `NoisyColors`, and all others are noisy around the background
+/// plate with its own noise. Pixels that the foreground changes without covering them, like its shadow, fall to
+/// the outliers of the plate.
+pub struct PlateColors { pub noise: PixelNoise, pub mask: Mask, pub plate: BackgroundPlate }
+
+impl PlateColors {
+    /// the render on `foreground`, the plate elsewhere, and the noise of each pixel
+    fn pixel_params<'a>(&'a self, pixels: &'a Colors, foreground: &'a Mask, outlier_prob: f32, sigma: f32)
+        -> impl Fn(usize) -> (&'a Color, f32, f32) + Sync + 'a {
+        move |i| {
+            if foreground.contains(i % W, i / W) {
+                (&pixels[i], outlier_prob, sigma)
+            } else {
+                (&self.plate.colors[i], self.plate.outlier_prob, self.plate.sigma)
+            }
+        }
+    }
+
+    /// per-pixel log-likelihoods of the observed colors, zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Colors, params: (Colors,Mask,f32,f32)) -> Vec<f64> {
+        let (pixels, foreground, outlier_prob, sigma) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let pixel_params = self.pixel_params(&pixels, &foreground, outlier_prob, sigma);
+        pixel_log_likelihoods(AREA, |i, cache| {
+            if !self.mask.contains(i % W, i / W) { return 0.; }
+            let (true_p, outlier_prob, sigma) = pixel_params(i);
+            (0..=2).map(|c| pixel.cached_logpdf(&noisy_pixels[i][c], (true_p[c], outlier_prob, sigma), cache)).sum()
+        })
+    }
+}
+
+impl PlateColors {
+    /// per-pixel posterior probabilities that any channel of the observed color is an outlier, of the render or of
+    /// the plate
+    pub fn outlier_posteriors(&self, noisy_pixels: &Colors, params: (Colors,Mask,f32,f32)) -> Depths {
+        let (pixels, foreground, outlier_prob, sigma) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let pixel_params = self.pixel_params(&pixels, &foreground, outlier_prob, sigma);
+        (0..AREA)
+            .map(|i| {
+                let (true_p, outlier_prob, sigma) = pixel_params(i);
+                let inlier_prob = (0..=2)
+                    .map(|c| 1. - pixel.outlier_posterior(&noisy_pixels[i][c], (true_p[c], outlier_prob, sigma)))
+                    .product::<f64>();
+                (1. - inlier_prob) as f32
+            })
+            .collect()
+    }
+}
+
+impl Distribution<Colors,(Colors,Mask,f32,f32)> for PlateColors {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,Mask,f32,f32)) -> f64 {
+        self.log_likelihood_map(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,Mask,f32,f32)) -> Colors {
+        let (pixels, foreground, outlier_prob, sigma) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let pixel_params = self.pixel_params(&pixels, &foreground, outlier_prob, sigma);
+        (0..AREA)
+            .map(|i| {
+                let (true_p, outlier_prob, sigma) = pixel_params(i);
+                [0, 1, 2].map(|c| pixel.random(rng, (true_p[c], outlier_prob, sigma)))
+            })
+            .collect()
+    }
+}
+
+/// depth sensor pixel distribution type, with params `(mu, dropout_prob, outlier_prob, sigma)`: with probability
+/// `outlier_prob` a uniform spurious reading on [0, 1], otherwise a missing (zero) reading with probability
+/// `dropout_prob`, and otherwise a truncated Gaussian around the true value. Zero readings are scored by their
+/// probability mass rather than a density.
+pub struct SensorPixel { }
+pub const sensor_pixel: SensorPixel = SensorPixel { };
+
+impl Distribution<f32,(f32,f32,f32,f32)> for SensorPixel {
+    fn logpdf(&self, x: &f32, params: (f32,f32,f32,f32)) -> f64 {
+        let (mu, dropout_prob, outlier_prob, sigma) = params;
+        let (dropout_prob, outlier_prob) = (dropout_prob as f64, outlier_prob as f64);
+        if *x == 0. {
+            return ((1. - outlier_prob) * dropout_prob).ln();
+        }
+        if !(0.0 < *x && *x <= 1.0) {
+            return f64::NEG_INFINITY;
+        }
+        let w_outlier = outlier_prob.ln();
+        let w_inlier = ((1. - outlier_prob) * (1. - dropout_prob)).ln() + truncated_normal.logpdf(x, (mu, sigma, 0.0, 1.0));
+        let w_max = w_outlier.max(w_inlier);
+        if w_max == f64::NEG_INFINITY {
+            return w_max;
+        }
+        w_max + ((w_outlier - w_max).exp() + (w_inlier - w_max).exp()).ln()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32,f32)) -> f32 {
+        let (mu, dropout_prob, outlier_prob, sigma) = params;
+        if u01(rng) < outlier_prob as f64 {
+            u01(rng) as f32
+        } else if u01(rng) < dropout_prob as f64 {
+            0.0
+        } else {
+            truncated_normal.random(rng, (mu, sigma, 0.0, 1.0))
+        }
+    }
+}
+
+impl SensorPixel {
+    /// posterior probability that `x` is a spurious reading
+    pub fn outlier_posterior(&self, x: &f32, params: (f32,f32,f32,f32)) -> f64 {
+        let (_, _, outlier_prob, _) = params;
+        if *x == 0. || outlier_prob <= 0. {
+            return 0.;
+        }
+        ((outlier_prob as f64).ln() - self.logpdf(x, params)).exp().min(1.)
+    }
+}
+
+/// noise characteristics of a structured-light or time-of-flight depth sensor, in meters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthSensor {
+    /// standard deviation `sigma0 + sigma2 * z^2` of a reading at metric depth `z`
+    pub sigma0: f32,
+    pub sigma2: f32,
+    /// probability of a missing return on a smooth surface, and at a depth discontinuity
+    pub dropout_prob: f32,
+    pub edge_dropout_prob: f32,
+    /// jump in metric depth to a neighbouring pixel above which a pixel lies on a depth discontinuity
+    pub edge_threshold: f32,
+    pub outlier_prob: f32
+}
+
+impl Default for DepthSensor {
+    fn default() -> Self {
+        DepthSensor {
+            sigma0: 0.0015,
+            sigma2: 0.0025,
+            dropout_prob: 0.02,
+            edge_dropout_prob: 0.5,
+            edge_threshold: 0.1,
+            outlier_prob: 0.01
+        }
+    }
+}
+
+/// metric depth of a rendered depth value, which must be a hit (nonzero)
+fn metric_depth(p: f32) -> f32 {
+    NEAR + (1. - p) * (FAR - NEAR)
+}
+
+impl DepthSensor {
+    /// per-pixel `sensor_pixel` params for rendered depths: misses never return a reading, and pixels on depth
+    /// discontinuities drop out more often
+    pub fn pixel_params(&self, pixels: &Depths) -> Vec<(f32,f32,f32,f32)> {
+        let mut params = Vec::with_capacity(AREA);
+        for y in 0..H {
+            for x in 0..W {
+                let p = pixels[y*W + x];
+                if p == 0. {
+                    params.push((p, 1.0, self.outlier_prob, 1.0));
+                    continue;
+                }
+                let z = metric_depth(p);
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                let on_edge = neighbours.iter()
+                    .filter(|&&(nx, ny)| nx < W && ny < H)
+                    .any(|&(nx, ny)| {
+                        let q = pixels[ny*W + nx];
+                        q == 0. || (metric_depth(q) - z).abs() > self.edge_threshold
+                    });
+                let dropout_prob = if on_edge { self.edge_dropout_prob } else { self.dropout_prob };
+                let sigma = (self.sigma0 + self.sigma2 * z * z) / (FAR - NEAR);
+                params.push((p, dropout_prob, self.outlier_prob, sigma));
+            }
+        }
+        params
+    }
+}
+
+/// depth sensor image distribution type
+pub struct SensorDepths { pub mask: Mask }
+pub const sensor_depths: SensorDepths = SensorDepths { mask: Mask::All };
+
+impl Distribution<Depths,(Depths,DepthSensor)> for SensorDepths {
+    fn logpdf(&self, noisy_pixels: &Depths, params: (Depths,DepthSensor)) -> f64 {
+        self.log_likelihood_map(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Depths,DepthSensor)) -> Depths {
+        let (pixels, sensor) = params;
+        sensor.pixel_params(&pixels).into_iter()
+            .map(|params| sensor_pixel.random(rng, params))
+            .collect()
+    }
+}
+
+impl SensorDepths {
+    /// per-pixel log-likelihoods of the observed depths, zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Depths, params: (Depths,DepthSensor)) -> Vec<f64> {
+        let (pixels, sensor) = params;
+        noisy_pixels.iter().zip(sensor.pixel_params(&pixels)).enumerate()
+            .map(|(i, (noisy_p, params))| if self.mask.contains(i % W, i / W) { sensor_pixel.logpdf(noisy_p, params) } else { 0. })
+            .collect()
+    }
+}
+
+/// noisy RGB-D distribution type, with params `(pixels, color_outlier_prob, color_sigma, depth_outlier_prob, depth_sigma)`.
+/// Colors and depths are scored independently at pyramid level `level`, the depths by `depth_sensor` at full
+/// resolution instead when it is set.
+pub struct NoisyRgbd { pub noise: PixelNoise, pub mask: Mask, pub level: usize, pub depth_sensor: Option<DepthSensor> }
+pub const noisy_rgbd: NoisyRgbd = NoisyRgbd { noise: PixelNoise::Gaussian, mask: Mask::All, level: 0, depth_sensor: None };
+
+impl Distribution<Rgbd,(Rgbd,f32,f32,f32,f32)> for NoisyRgbd {
+    fn logpdf(&self, noisy_pixels: &Rgbd, params: (Rgbd,f32,f32,f32,f32)) -> f64 {
+        let ((colors, depths), color_outlier_prob, color_sigma, depth_outlier_prob, depth_sigma) = params;
+        let w_colors = PyramidColors { noise: self.noise, mask: self.mask.clone(), level: self.level }
+            .logpdf(&noisy_pixels.0, (colors, color_outlier_prob, color_sigma));
+        let w_depths = match self.depth_sensor {
+            Some(sensor) => SensorDepths { mask: self.mask.clone() }.logpdf(&noisy_pixels.1, (depths, sensor)),
+            None => PyramidDepths { noise: self.noise, mask: self.mask.clone(), level: self.level }
+                .logpdf(&noisy_pixels.1, (depths, depth_outlier_prob, depth_sigma))
+        };
+        w_colors + w_depths
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Rgbd,f32,f32,f32,f32)) -> Rgbd {
+        let ((colors, depths), color_outlier_prob, color_sigma, depth_outlier_prob, depth_sigma) = params;
+        let colors = PyramidColors { noise: self.noise, mask: self.mask.clone(), level: self.level }
+            .random(rng, (colors, color_outlier_prob, color_sigma));
+        let depths = match self.depth_sensor {
+            Some(sensor) => SensorDepths { mask: self.mask.clone() }.random(rng, (depths, sensor)),
+            None => PyramidDepths { noise: self.noise, mask: self.mask.clone(), level: self.level }
+                .random(rng, (depths, depth_outlier_prob, depth_sigma))
+        };
+        (colors, depths)
+    }
+}
+
+
+/* image pyramids */
+
+/// averages `2^level x 2^level` blocks of a depth image into an image of `(W >> level) x (H >> level)` pixels
+pub fn downsample_depths(pixels: &Depths, level: usize) -> Depths {
+    let (w, k) = (W >> level, 1 << level);
+    let mut coarse = vec![0.0; (H >> level) * w];
+    for y in 0..H {
+        for x in 0..W {
+            coarse[(y >> level) * w + (x >> level)] += pixels[y*W + x];
+        }
+    }
+    coarse.iter().map(|p| p / (k * k) as f32).collect()
+}
+
+/// averages `2^level x 2^level` blocks of a color image into an image of `(W >> level) x (H >> level)` pixels
+pub fn downsample_colors(pixels: &Colors, level: usize) -> Colors {
+    let (w, k) = (W >> level, 1 << level);
+    let mut coarse = vec![[0.0; 3]; (H >> level) * w];
+    for y in 0..H {
+        for x in 0..W {
+            coarse[(y >> level) * w + (x >> level)] = vec3_add(coarse[(y >> level) * w + (x >> level)], pixels[y*W + x]);
+        }
+    }
+    coarse.iter().map(|c| vec3_scale(c, 1.0 / (k * k) as f32)).collect()
+}
+
+fn upsample<T: Copy>(coarse: &[T], level: usize) -> Vec<T> {
+    (0..AREA).map(|i| coarse[((i / W) >> level) * (W >> level) + ((i % W) >> level)]).collect()
+}
+
+/// repeats each pixel of a depth image of `(W >> level) x (H >> level)` pixels over a `2^level x 2^level` block
+pub fn upsample_depths(coarse: &Depths, level: usize) -> Depths {
+    upsample(coarse, level)
+}
+
+/// repeats each pixel of a color image of `(W >> level) x (H >> level)` pixels over a `2^level x 2^level` block
+pub fn upsample_colors(coarse: &Colors, level: usize) -> Colors {
+    upsample(coarse, level)
+}
+
+/// keeps the blocks of `2^level x 2^level` pixels of which the mask keeps the majority
+fn downsample_mask(mask: &Mask, level: usize) -> Vec<bool> {
+    let (w, k) = (W >> level, 1 << level);
+    let mut counts = vec![0; (H >> level) * w];
+    for y in 0..H {
+        for x in 0..W {
+            if mask.contains(x, y) { counts[(y >> level) * w + (x >> level)] += 1; }
+        }
+    }
+    counts.iter().map(|&n| 2 * n > k * k).collect()
+}
+
+/// pyramid depth distribution type: scores the observed and true images downsampled by `2^level` like
+/// `NoisyDepths`, which flattens the likelihood surface at coarse levels. Samples are noisy at the downsampled
+/// resolution and upsampled back, so that they downsample to what is scored.
+pub struct PyramidDepths { pub noise: PixelNoise, pub mask: Mask, pub level: usize }
+
+impl PyramidDepths {
+    /// log-likelihoods of the downsampled pixels, zero outside the downsampled mask
+    fn coarse_log_likelihoods(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> Vec<f64> {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let noisy_pixels = downsample_depths(noisy_pixels, self.level);
+        let pixels = downsample_depths(&pixels, self.level);
+        let mask = downsample_mask(&self.mask, self.level);
+        pixel_log_likelihoods(mask.len(), |i, cache| {
+            if !mask[i] { return 0.; }
+            pixel.cached_logpdf(&noisy_pixels[i], (pixels[i], outlier_prob, noise), cache)
+        })
+    }
+
+    /// per-pixel log-likelihoods of the observed depths at full resolution, where each block shares that of its
+    /// downsampled pixel equally, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> Vec<f64> {
+        if self.level == 0 {
+            return NoisyDepths { noise: self.noise, mask: self.mask.clone() }.log_likelihood_map(noisy_pixels, params);
+        }
+        let k2 = (1 << (2 * self.level)) as f64;
+        upsample(&self.coarse_log_likelihoods(noisy_pixels, params), self.level).iter().map(|w| w / k2).collect()
+    }
+
+    /// per-pixel posterior probabilities that the downsampled observed depth of their block is an outlier
+    pub fn outlier_posteriors(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let (noisy_pixels, pixels) = (downsample_depths(noisy_pixels, self.level), downsample_depths(&pixels, self.level));
+        let coarse = NoisyDepths { noise: self.noise, mask: Mask::All }.outlier_posteriors(&noisy_pixels, (pixels, outlier_prob, noise));
+        upsample(&coarse, self.level)
+    }
+}
+
+impl Distribution<Depths,(Depths,f32,f32)> for PyramidDepths {
+    fn logpdf(&self, noisy_pixels: &Depths, params: (Depths,f32,f32)) -> f64 {
+        if self.level == 0 {
+            return NoisyDepths { noise: self.noise, mask: self.mask.clone() }.logpdf(noisy_pixels, params);
+        }
+        self.coarse_log_likelihoods(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Depths,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let coarse = downsample_depths(&pixels, self.level).iter()
+            .map(|p| pixel.random(rng, (*p, outlier_prob, noise)))
+            .collect();
+        upsample_depths(&coarse, self.level)
+    }
+}
+
+/// pyramid color distribution type: scores the observed and true images downsampled by `2^level` like
+/// `NoisyColors`, which flattens the likelihood surface at coarse levels. Samples are noisy at the downsampled
+/// resolution and upsampled back, so that they downsample to what is scored.
+pub struct PyramidColors { pub noise: PixelNoise, pub mask: Mask, pub level: usize }
+
+impl PyramidColors {
+    /// log-likelihoods of the downsampled pixels, zero outside the downsampled mask
+    fn coarse_log_likelihoods(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Vec<f64> {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let noisy_pixels = downsample_colors(noisy_pixels, self.level);
+        let pixels = downsample_colors(&pixels, self.level);
+        let mask = downsample_mask(&self.mask, self.level);
+        pixel_log_likelihoods(mask.len(), |i, cache| {
+            if !mask[i] { return 0.; }
+            (0..=2).map(|c| pixel.cached_logpdf(&noisy_pixels[i][c], (pixels[i][c], outlier_prob, noise), cache)).sum()
+        })
+    }
+
+    /// per-pixel log-likelihoods of the observed colors at full resolution, where each block shares that of its
+    /// downsampled pixel equally, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Vec<f64> {
+        if self.level == 0 {
+            return NoisyColors { noise: self.noise, mask: self.mask.clone() }.log_likelihood_map(noisy_pixels, params);
+        }
+        let k2 = (1 << (2 * self.level)) as f64;
+        upsample(&self.coarse_log_likelihoods(noisy_pixels, params), self.level).iter().map(|w| w / k2).collect()
+    }
+
+    /// per-pixel posterior probabilities that any channel of the downsampled observed color of their block is an
+    /// outlier
+    pub fn outlier_posteriors(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Depths {
+        let (pixels, outlier_prob, noise) = params;
+        let (noisy_pixels, pixels) = (downsample_colors(noisy_pixels, self.level), downsample_colors(&pixels, self.level));
+        let coarse = NoisyColors { noise: self.noise, mask: Mask::All }.outlier_posteriors(&noisy_pixels, (pixels, outlier_prob, noise));
+        upsample(&coarse, self.level)
+    }
+}
+
+impl Distribution<Colors,(Colors,f32,f32)> for PyramidColors {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> f64 {
+        if self.level == 0 {
+            return NoisyColors { noise: self.noise, mask: self.mask.clone() }.logpdf(noisy_pixels, params);
+        }
+        self.coarse_log_likelihoods(noisy_pixels, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,f32,f32)) -> Colors {
+        let (pixels, outlier_prob, noise) = params;
+        let pixel = NoisyPixel { noise: self.noise };
+        let coarse = downsample_colors(&pixels, self.level).iter()
+            .map(|p| p.map(|c| pixel.random(rng, (c, outlier_prob, noise))))
+            .collect();
+        upsample_colors(&coarse, self.level)
+    }
+}
+
+
+/* edge features */
+
+/// Sobel gradients of a grayscale image, with magnitudes scaled to [0, 1] and borders replicated
+pub fn sobel(pixels: &Depths) -> Edges {
+    let p = |x: isize, y: isize| pixels[(y.clamp(0, H as isize - 1) as usize)*W + x.clamp(0, W as isize - 1) as usize];
+    let mut magnitudes = vec![0.0; AREA];
+    let mut orientations = vec![0.0; AREA];
+    for y in 0..H as isize {
+        for x in 0..W as isize {
+            let gx = p(x+1, y-1) + 2.0*p(x+1, y) + p(x+1, y+1) - p(x-1, y-1) - 2.0*p(x-1, y) - p(x-1, y+1);
+            let gy = p(x-1, y+1) + 2.0*p(x, y+1) + p(x+1, y+1) - p(x-1, y-1) - 2.0*p(x, y-1) - p(x+1, y-1);
+            let i = y as usize*W + x as usize;
+            magnitudes[i] = (gx.hypot(gy) / (4.0 * 2f32.sqrt())).min(1.0);
+            orientations[i] = gy.atan2(gx);
+        }
+    }
+    (magnitudes, orientations)
+}
+
+/// Sobel gradients of the luma of a color image
+pub fn edge_map(pixels: &Colors) -> Edges {
+    sobel(&pixels.iter().map(|c| luma(*c)).collect())
+}
+
+/// von Mises distribution type on angles in [-pi, pi], with params `(mu, kappa)`
+pub struct VonMises { }
+pub const von_mises: VonMises = VonMises { };
+
+/// log of the modified Bessel function of the first kind `I_0` (Abramowitz & Stegun 9.8.1 and 9.8.2)
+fn log_bessel_i0(x: f64) -> f64 {
+    let x = x.abs();
+    if x < 3.75 {
+        let t = (x / 3.75) * (x / 3.75);
+        (1. + t*(3.5156229 + t*(3.0899424 + t*(1.2067492 + t*(0.2659732 + t*(0.0360768 + t*0.0045813)))))).ln()
+    } else {
+        let t = 3.75 / x;
+        let series = 0.39894228 + t*(0.01328592 + t*(0.00225319 + t*(-0.00157565 + t*(0.00916281
+            + t*(-0.02057706 + t*(0.02635537 + t*(-0.01647633 + t*0.00392377)))))));
+        x - 0.5 * x.ln() + series.ln()
+    }
+}
+
+impl Distribution<f32,(f32,f32)> for VonMises {
+    fn logpdf(&self, x: &f32, params: (f32,f32)) -> f64 {
+        let (mu, kappa) = (params.0 as f64, params.1 as f64);
+        kappa * ((*x as f64) - mu).cos() - (2. * PI64).ln() - log_bessel_i0(kappa)
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (f32,f32)) -> f32 {
+        let (mu, kappa) = (params.0 as f64, params.1 as f64);
+        if kappa < 1e-6 {
+            return (PI64 * (2. * u01(rng) - 1.)) as f32;
+        }
+        // rejection sampler of Best & Fisher (1979)
+        let tau = 1. + (1. + 4. * kappa * kappa).sqrt();
+        let rho = (tau - (2. * tau).sqrt()) / (2. * kappa);
+        let r = (1. + rho * rho) / (2. * rho);
+        let f = loop {
+            let z = (PI64 * u01(rng)).cos();
+            let f = (1. + r * z) / (r + z);
+            let c = kappa * (r - f);
+            let u = u01(rng);
+            if c * (2. - c) > u || (c / u).ln() + 1. >= c {
+                break f;
+            }
+        };
+        let theta = if u01(rng) < 0.5 { -f.acos() } else { f.acos() };
+        ((mu + theta + PI64).rem_euclid(2. * PI64) - PI64) as f32
+    }
+}
+
+/// parameters of `NoisyEdges`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeNoise {
+    /// probability of a uniform outlier, for magnitudes and orientations alike
+    pub outlier_prob: f32,
+    /// noise of the gradient magnitudes
+    pub sigma: f32,
+    /// concentration of the gradient orientations
+    pub kappa: f32,
+    /// true gradient magnitude below which the orientation is uniform
+    pub min_magnitude: f32
+}
+
+impl Default for EdgeNoise {
+    fn default() -> Self {
+        EdgeNoise { outlier_prob: 0.1, sigma: 0.05, kappa: 4.0, min_magnitude: 0.05 }
+    }
+}
+
+/// noisy edge map distribution type, with params `(edges, edge_noise)`: magnitudes are noisy pixels, and where
+/// the true gradient is strong enough, orientations are von Mises around the true orientation up to outliers.
+/// Scores silhouettes and texture boundaries rather than flat regions, and orientations are invariant to
+/// the global lighting.
+pub struct NoisyEdges { pub mask: Mask }
+pub const noisy_edges: NoisyEdges = NoisyEdges { mask: Mask::All };
+
+impl NoisyEdges {
+    fn orientation_logpdf(x: &f32, mu: f32, magnitude: f32, noise: EdgeNoise) -> f64 {
+        let w_uniform = -(2. * PI64).ln();
+        if magnitude < noise.min_magnitude {
+            return w_uniform;
+        }
+        let w_outlier = (noise.outlier_prob as f64).ln() + w_uniform;
+        let w_inlier = (1. - noise.outlier_prob as f64).ln() + von_mises.logpdf(x, (mu, noise.kappa));
+        let w_max = w_outlier.max(w_inlier);
+        w_max + ((w_outlier - w_max).exp() + (w_inlier - w_max).exp()).ln()
+    }
+}
+
+impl NoisyEdges {
+    /// per-pixel log-likelihoods of the observed edges, zero outside the mask, which sum to `logpdf`
+    pub fn log_likelihood_map(&self, noisy: &Edges, params: (Edges,EdgeNoise)) -> Vec<f64> {
+        let ((magnitudes, orientations), noise) = params;
+        pixel_log_likelihoods(AREA, |i, cache| {
+            if !self.mask.contains(i % W, i / W) { return 0.; }
+            noisy_pixel.cached_logpdf(&noisy.0[i], (magnitudes[i], noise.outlier_prob, noise.sigma), cache)
+                + Self::orientation_logpdf(&noisy.1[i], orientations[i], magnitudes[i], noise)
+        })
+    }
+}
+
+impl Distribution<Edges,(Edges,EdgeNoise)> for NoisyEdges {
+    fn logpdf(&self, noisy: &Edges, params: (Edges,EdgeNoise)) -> f64 {
+        self.log_likelihood_map(noisy, params).iter().sum()
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Edges,EdgeNoise)) -> Edges {
+        let ((magnitudes, orientations), noise) = params;
+        let uniform_angle = |rng: &mut ThreadRng| (PI64 * (2. * u01(rng) - 1.)) as f32;
+        magnitudes.iter().zip(orientations.iter())
+            .map(|(m, o)| {
+                let noisy_m = noisy_pixel.random(rng, (*m, noise.outlier_prob, noise.sigma));
+                let noisy_o = if *m < noise.min_magnitude || u01(rng) < noise.outlier_prob as f64 {
+                    uniform_angle(rng)
+                } else {
+                    von_mises.random(rng, (*o, noise.kappa))
+                };
+                (noisy_m, noisy_o)
+            })
+            .unzip()
+    }
+}
+
+
+/* approximate Bayesian computation */
+
+/// a summary statistic of a color image, as a vector of values in about [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SummaryStatistic {
+    /// fraction of pixels in each of `bins` equal bins per channel
+    Histogram { bins: usize },
+    /// mean color of each cell of a `rows` by `cols` grid
+    RegionMeans { rows: usize, cols: usize },
+    /// area, centroid, standard deviations and correlation of the silhouette, the pixels farther than `threshold`
+    /// from the median color of the image (which is taken to be the background)
+    SilhouetteMoments { threshold: f32 }
+}
+
+impl SummaryStatistic {
+    /// the statistic of the pixels in `mask`
+    pub fn summarize(&self, pixels: &Colors, mask: &Mask) -> Vec<f32> {
+        let indices = || (0..AREA).filter(|i| mask.contains(i % W, i / W));
+        match *self {
+            SummaryStatistic::Histogram { bins } => {
+                let mut counts = vec![0.0; 3 * bins];
+                for i in indices() {
+                    for c in 0..=2 {
+                        let bin = ((pixels[i][c].clamp(0.0, 1.0) * bins as f32) as usize).min(bins - 1);
+                        counts[c * bins + bin] += 1.0;
+                    }
+                }
+                let n = indices().count().max(1) as f32;
+                counts.iter().map(|count| count / n).collect()
+            }
+            SummaryStatistic::RegionMeans { rows, cols } => {
+                let mut sums = vec![[0.0; 3]; rows * cols];
+                let mut counts = vec![0.0; rows * cols];
+                for i in indices() {
+                    let cell = (i / W) * rows / H * cols + (i % W) * cols / W;
+                    sums[cell] = vec3_add(sums[cell], pixels[i]);
+                    counts[cell] += 1.0;
+                }
+                sums.iter().zip(counts.iter())
+                    .flat_map(|(sum, count)| vec3_scale(sum, 1.0 / f32::max(*count, 1.0)))
+                    .collect()
+            }
+            SummaryStatistic::SilhouetteMoments { threshold } => {
+                let median = [0, 1, 2].map(|c| {
+                    let mut values: Vec<f32> = indices().map(|i| pixels[i][c]).collect();
+                    values.sort_by(|a, b| a.total_cmp(b));
+                    values.get(values.len() / 2).copied().unwrap_or(0.0)
+                });
+                let silhouette: Vec<(f32, f32)> = indices()
+                    .filter(|i| vec3_norm(&vec3_sub(pixels[*i], median)) > threshold)
+                    .map(|i| (((i % W) as f32 + 0.5) / W as f32, ((i / W) as f32 + 0.5) / H as f32))
+                    .collect();
+                if silhouette.is_empty() {
+                    return vec![0.0, 0.5, 0.5, 0.0, 0.0, 0.0];
+                }
+                let n = silhouette.len() as f32;
+                let (cx, cy) = silhouette.iter().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+                let (cx, cy) = (cx / n, cy / n);
+                let (mut vx, mut vy, mut cov) = (0.0, 0.0, 0.0);
+                for (x, y) in silhouette.iter() {
+                    vx += (x - cx) * (x - cx) / n;
+                    vy += (y - cy) * (y - cy) / n;
+                    cov += (x - cx) * (y - cy) / n;
+                }
+                let rho = if vx > 0.0 && vy > 0.0 { cov / (vx * vy).sqrt() } else { 0.0 };
+                vec![n / mask.count() as f32, cx, cy, vx.sqrt(), vy.sqrt(), rho]
+            }
+        }
+    }
+}
+
+/// the kernel with which `AbcColors` compares summaries
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AbcKernel {
+    /// an isotropic Gaussian with standard deviation `tolerance`
+    #[default]
+    Gaussian,
+    /// uniform on the ball of radius `tolerance`, so summaries any farther are rejected outright
+    Uniform
+}
+
+/// approximate likelihood of a color image from its summary statistics, with params `(pixels, tolerance)`: the
+/// concatenated summaries of the observed image are `kernel`-distributed around those of the render. Unlike the
+/// pixel likelihoods it doesn't assume independent pixel noise, so it isn't overconfident on very noisy images.
+/// Since the kernel isn't a distribution over images, `random` simulates one as the render plus Gaussian pixel noise
+/// with standard deviation `tolerance`, whose summaries stay near those of the render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbcColors { pub stats: Vec<SummaryStatistic>, pub kernel: AbcKernel, pub mask: Mask }
+
+impl AbcColors {
+    /// the concatenated summary statistics of an image
+    pub fn summarize(&self, pixels: &Colors) -> Vec<f32> {
+        self.stats.iter().flat_map(|stat| stat.summarize(pixels, &self.mask)).collect()
+    }
+
+    /// log-density of the kernel at the difference between two summaries
+    pub fn kernel_logpdf(&self, noisy: &[f32], summary: &[f32], tolerance: f32) -> f64 {
+        let (k, tolerance) = (summary.len() as f64, tolerance as f64);
+        let d2: f64 = noisy.iter().zip(summary.iter()).map(|(a, b)| ((a - b) as f64).powi(2)).sum();
+        match self.kernel {
+            AbcKernel::Gaussian => -0.5 * d2 / (tolerance * tolerance) - k * (tolerance * (2. * PI64).sqrt()).ln(),
+            AbcKernel::Uniform if d2 <= tolerance * tolerance => {
+                // minus the log volume of the k-ball
+                lgamma(0.5 * k + 1.) - 0.5 * k * PI64.ln() - k * tolerance.ln()
+            }
+            AbcKernel::Uniform => f64::NEG_INFINITY
+        }
+    }
+}
+
+impl Distribution<Colors,(Colors,f32)> for AbcColors {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,f32)) -> f64 {
+        let (pixels, tolerance) = params;
+        self.kernel_logpdf(&self.summarize(noisy_pixels), &self.summarize(&pixels), tolerance)
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,f32)) -> Colors {
+        let (pixels, tolerance) = params;
+        noisy_colors.random(rng, (pixels, 0.0, tolerance))
+    }
+}
+
+
+/* dynamic generative functions */
+
+/// image features observed by `sphere_color_model`, `ball_model` and `shape_model`: the pixels at `"observation"`,
+/// and their `edge_map` at `"edges"` (and by `stereo_ball_model` at `"observation_{i}"` and `"edges_{i}"` for camera
+/// `i` of its rig, while `rgbd_ball_model` always observes its pixels only)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Features {
+    #[default]
+    Pixels,
+    Edges,
+    PixelsAndEdges
+}
+
+/// priors of the observation noise of `grounded_depth_model` and of the colors of the other models, which then infer
+/// the outlier rate at `"outlier_prob"` and the log of the pixel noise at `"log_pixel_noise"`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoisePrior {
+    /// the outlier rate is uniform on `[0, max_outlier_prob]`
+    pub max_outlier_prob: f64,
+    /// mean and standard deviation of the normal prior of the log pixel noise
+    pub log_sigma: (f64, f64)
+}
+
+impl Default for NoisePrior {
+    fn default() -> Self {
+        NoisePrior { max_outlier_prob: 0.5, log_sigma: ((PIXEL_NOISE as f64).ln(), 1.0) }
+    }
+}
+
+/// categorical distribution type over the indices of its probabilities, under which any other integer is impossible
+/// rather than out of bounds
+pub struct CheckedCategorical { }
+pub const checked_categorical: CheckedCategorical = CheckedCategorical { };
+
+impl Distribution<i64,Vec<f64>> for CheckedCategorical {
+    fn logpdf(&self, x: &i64, params: Vec<f64>) -> f64 {
+        if 0 <= *x && (*x as usize) < params.len() {
+            categorical.logpdf(x, params)
         } else {
             f64::NEG_INFINITY
         }
     }
 
-    fn random(&self, rng: &mut ThreadRng, params: (f32,f32,f32,f32)) -> f32 {
-        let (mu, sigma, a, b) = params;
-        let mut x = normal.random(rng, (mu as f64, sigma as f64)) as f32;
-        while !(a <= x && x <= b) {  // rejection sampling
-            x = normal.random(rng, (mu as f64, sigma as f64)) as f32;
+    fn random(&self, rng: &mut ThreadRng, params: Vec<f64>) -> i64 {
+        categorical.random(rng, params)
+    }
+}
+
+/// the primitive of the object in `shape_model`, chosen at `"shape"` by its index
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeClass {
+    /// of radius `"sphere_radius"`
+    Sphere,
+    /// of half side lengths `"cuboid_half_x"`, `"cuboid_half_y"` and `"cuboid_half_z"`, turned by `"cuboid_yaw"`
+    Cuboid,
+    /// upright, of radius `"cylinder_radius"` and half height `"cylinder_half_height"`
+    Cylinder
+}
+
+impl ShapeClass {
+    pub const ALL: [ShapeClass; 3] = [ShapeClass::Sphere, ShapeClass::Cuboid, ShapeClass::Cylinder];
+
+    pub fn index(&self) -> i64 {
+        *self as i64
+    }
+
+    /// the addresses of the sizes of an object of this class, in the order that `solid` takes them
+    pub fn size_addrs(&self) -> &'static [&'static str] {
+        match self {
+            ShapeClass::Sphere => &["sphere_radius"],
+            ShapeClass::Cuboid => &["cuboid_half_x", "cuboid_half_y", "cuboid_half_z", "cuboid_yaw"],
+            ShapeClass::Cylinder => &["cylinder_radius", "cylinder_half_height"]
+        }
+    }
+
+    /// an object of this class standing on the table at `(u, v)`, of the sizes at `size_addrs`
+    pub fn solid(&self, u: f32, v: f32, sizes: &[f64]) -> Box<dyn Solid> {
+        let size = |i: usize| sizes[i] as f32;
+        match self {
+            ShapeClass::Sphere => Box::new(Sphere { center: [u, size(0), v], radius: size(0) }),
+            ShapeClass::Cuboid => {
+                let half_extents = [size(0), size(1), size(2)];
+                let rotation = quat_from_rotvec([0.0, size(3), 0.0]);
+                Box::new(Cuboid { center: [u, half_extents[1], v], half_extents, rotation })
+            }
+            ShapeClass::Cylinder => {
+                let (radius, half_height) = (size(0), size(1));
+                Box::new(Cylinder { center: [u, half_height, v], radius, half_height, rotation: quat_id() })
+            }
+        }
+    }
+}
+
+impl TryFrom<i64> for ShapeClass {
+    type Error = i64;
+
+    /// the class of an index, or the index back if no class has it
+    fn try_from(index: i64) -> Result<Self, i64> {
+        usize::try_from(index).ok().and_then(|i| ShapeClass::ALL.get(i).copied()).ok_or(index)
+    }
+}
+
+/// settings of the observation likelihood, passed as the argument of the models
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObsParams {
+    pub noise: PixelNoise,
+    pub outlier_prob: f32,
+    pub sigma: f32,
+    /// pixel noise of depth observations, separate from that of colors
+    pub depth_outlier_prob: f32,
+    pub depth_sigma: f32,
+    /// scores depth observations with a depth sensor model instead of pixel noise
+    pub depth_sensor: Option<DepthSensor>,
+    pub mask: Mask,
+    /// scores colors in the lightness and chroma of a perceptual color space, with `sigma` as the lightness noise
+    /// (except in `rgbd_ball_model`, which scores colors channel-wise)
+    pub color_space: Option<ColorSpace>,
+    pub chroma_sigma: f32,
+    pub features: Features,
+    pub edge_noise: EdgeNoise,
+    /// infers the outlier rate and pixel noise instead of using the fixed ones above
+    pub noise_prior: Option<NoisePrior>,
+    /// scores the pixels that `sphere_color_model`, `ball_model` and `shape_model` don't cover with their foreground
+    /// object against this plate instead of the render, channel-wise at full resolution. `stereo_ball_model`,
+    /// whose views don't share one background, and `rgbd_ball_model` score their renders regardless.
+    pub background: Option<BackgroundPlate>,
+    /// infers the full camera orientation of the models of the ball and the shape at `"cam_rotation"`, under an
+    /// `isotropic_so3` prior with this concentration around looking straight ahead, instead of only its yaw at
+    /// `"cam_yaw"`
+    pub cam_rotation: Option<f32>,
+    /// scores the pixels of `sphere_color_model`, `ball_model`, `shape_model` and of each view of
+    /// `stereo_ball_model` by their summary statistics instead, with kernel width `abc_tolerance` (`rgbd_ball_model`
+    /// scores its pixels with `NoisyRgbd` regardless); not reflected in the per-pixel posterior summaries
+    pub abc: Option<AbcColors>,
+    pub abc_tolerance: f32,
+    /// pyramid level at which channel-wise pixel noise is scored, 0 for full resolution (perceptual colors, plates
+    /// and summary statistics are scored at full resolution)
+    pub level: usize,
+    /// inverse temperature of the likelihood, 1 for the posterior
+    pub inv_temp: f64
+}
+
+impl Default for ObsParams {
+    fn default() -> Self {
+        ObsParams {
+            noise: PixelNoise::Gaussian,
+            outlier_prob: OUTLIER_PROB,
+            sigma: PIXEL_NOISE,
+            depth_outlier_prob: OUTLIER_PROB,
+            depth_sigma: PIXEL_NOISE,
+            depth_sensor: None,
+            mask: Mask::All,
+            color_space: None,
+            chroma_sigma: PIXEL_NOISE,
+            features: Features::Pixels,
+            edge_noise: EdgeNoise::default(),
+            noise_prior: None,
+            background: None,
+            cam_rotation: None,
+            abc: None,
+            abc_tolerance: 0.05,
+            level: 0,
+            inv_temp: 1.0
+        }
+    }
+}
+
+/// the likelihood of the observed pixels of the color models that `ObsParams` selects, with params
+/// `(pixels, outlier_prob, sigma)`: summary statistics if it sets `abc`, a background plate around the coverage of
+/// the foreground if it sets `background`, perceptual coordinates if it sets `color_space`, and channel-wise pyramid
+/// noise otherwise
+pub enum ColorLikelihood {
+    Abc { dist: AbcColors, tolerance: f32 },
+    Plate { dist: PlateColors, foreground: Mask },
+    Perceptual { dist: PerceptualColors, chroma_sigma: f32 },
+    Pyramid(PyramidColors)
+}
+
+impl ColorLikelihood {
+    /// the likelihood that `obs` selects, where `foreground` computes the coverage of the foreground, which only a
+    /// background plate needs
+    pub fn new(obs: &ObsParams, foreground: impl FnOnce() -> Mask) -> Self {
+        if let Some(dist) = obs.abc.clone() {
+            return ColorLikelihood::Abc { dist, tolerance: obs.abc_tolerance };
+        }
+        if let Some(plate) = obs.background.clone() {
+            let dist = PlateColors { noise: obs.noise, mask: obs.mask.clone(), plate };
+            return ColorLikelihood::Plate { dist, foreground: foreground() };
+        }
+        match obs.color_space {
+            Some(space) => {
+                let dist = PerceptualColors { space, mask: obs.mask.clone() };
+                ColorLikelihood::Perceptual { dist, chroma_sigma: obs.chroma_sigma }
+            }
+            None => ColorLikelihood::Pyramid(PyramidColors { noise: obs.noise, mask: obs.mask.clone(), level: obs.level })
         }
-        x
     }
 }
 
-/// noisy depth distribution type
-struct NoisyDepths { }
-const noisy_depths: NoisyDepths = NoisyDepths { };
-
-impl Distribution<Depths,(Depths,f32)> for NoisyDepths {
-    fn logpdf(&self, noisy_pixels: &Depths, pixels_and_noise: (Depths,f32)) -> f64 {
-        let (pixels, noise) = pixels_and_noise;
-        let mut w = 0.;
-        for y in 0..H {
-            for x in 0..W {
-                let noisy_p = noisy_pixels[y*W + x];
-                let true_p = pixels[y*W + x];
-                w += truncated_normal.logpdf(&noisy_p, (true_p, noise, 0.0, 1.0))
+impl ColorLikelihood {
+    /// per-pixel log-likelihoods of the observed colors at full resolution, which sum to `logpdf`, or `None` for
+    /// summary statistics, which don't split into pixels
+    pub fn log_likelihood_map(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Option<Vec<f64>> {
+        let (pixels, outlier_prob, sigma) = params;
+        match self {
+            ColorLikelihood::Abc { .. } => None,
+            ColorLikelihood::Plate { dist, foreground } => {
+                Some(dist.log_likelihood_map(noisy_pixels, (pixels, foreground.clone(), outlier_prob, sigma)))
+            }
+            ColorLikelihood::Perceptual { dist, chroma_sigma } => {
+                Some(dist.log_likelihood_map(noisy_pixels, (pixels, outlier_prob, sigma, *chroma_sigma)))
             }
+            ColorLikelihood::Pyramid(dist) => Some(dist.log_likelihood_map(noisy_pixels, (pixels, outlier_prob, sigma)))
         }
-        (1. - noise as f64)*(w as f64)
     }
 
-    fn random(&self, rng: &mut ThreadRng, pixels_and_noise: (Depths,f32)) -> Depths {
-        let (pixels, noise) = pixels_and_noise;
-        let noise_f64 = noise as f64;
-        let mut noisy_pixels = vec![];
-        for y in 0..H {
-            for x in 0..W {
-                // Add mixture of noise from uniform and gaussian
-                if u01(rng) < noise_f64 {
-                    noisy_pixels.push(u01(rng) as f32);
-                } else {
-                    let noisy_p = truncated_normal.random(rng, (pixels[y*W + x], noise, 0.0, 1.0));
-                    noisy_pixels.push(noisy_p as f32);
-                }
+    /// per-pixel posterior probabilities that the observed colors are outliers, or `None` for summary statistics
+    pub fn outlier_posteriors(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> Option<Depths> {
+        let (pixels, outlier_prob, sigma) = params;
+        match self {
+            ColorLikelihood::Abc { .. } => None,
+            ColorLikelihood::Plate { dist, foreground } => {
+                Some(dist.outlier_posteriors(noisy_pixels, (pixels, foreground.clone(), outlier_prob, sigma)))
+            }
+            ColorLikelihood::Perceptual { dist, chroma_sigma } => {
+                Some(dist.outlier_posteriors(noisy_pixels, (pixels, outlier_prob, sigma, *chroma_sigma)))
             }
+            ColorLikelihood::Pyramid(dist) => Some(dist.outlier_posteriors(noisy_pixels, (pixels, outlier_prob, sigma)))
         }
-        noisy_pixels
     }
 }
 
-/// noisy (isotropic) color distribution type
-struct NoisyColors { }
-const noisy_colors: NoisyColors = NoisyColors { };
-
-impl Distribution<Colors,(Colors,f32)> for NoisyColors {
-    fn logpdf(&self, noisy_pixels: &Colors, pixels_and_noise: (Colors,f32)) -> f64 {
-        let (pixels, noise) = pixels_and_noise;
-        let mut w = 0.;
-        for y in 0..H {
-            for x in 0..W {
-                for i in 0..=2 {
-                    let noisy_p = noisy_pixels[y*W + x][i];
-                    let true_p = pixels[y*W + x][i];
-                    w += truncated_normal.logpdf(&noisy_p, (true_p, noise, 0.0, 1.0))
-                }
+impl Distribution<Colors,(Colors,f32,f32)> for ColorLikelihood {
+    fn logpdf(&self, noisy_pixels: &Colors, params: (Colors,f32,f32)) -> f64 {
+        let (pixels, outlier_prob, sigma) = params;
+        match self {
+            ColorLikelihood::Abc { dist, tolerance } => dist.logpdf(noisy_pixels, (pixels, *tolerance)),
+            ColorLikelihood::Plate { dist, foreground } => {
+                dist.logpdf(noisy_pixels, (pixels, foreground.clone(), outlier_prob, sigma))
+            }
+            ColorLikelihood::Perceptual { dist, chroma_sigma } => {
+                dist.logpdf(noisy_pixels, (pixels, outlier_prob, sigma, *chroma_sigma))
             }
+            ColorLikelihood::Pyramid(dist) => dist.logpdf(noisy_pixels, (pixels, outlier_prob, sigma))
         }
-        (1. - noise as f64)*(w as f64)
     }
 
-    fn random(&self, rng: &mut ThreadRng, pixels_and_noise: (Colors,f32)) -> Colors {
-        let (pixels, noise) = pixels_and_noise;
-        let noise_f64 = noise as f64;
-        let mut noisy_pixels = vec![];
-        for y in 0..H {
-            for x in 0..W {
-                // Add mixture of noise from uniform and gaussian
-                let mut noisy_p = vec3_zero();
-                for i in 0..=2 {
-                    if u01(rng) < noise_f64 {
-                        noisy_p[i] = u01(rng) as f32;
-                    } else {
-                        noisy_p[i] = truncated_normal.random(rng, (pixels[y*W + x][i], noise, 0., 1.)) as f32;
-                    }
-                }
-                noisy_pixels.push(noisy_p)
+    fn random(&self, rng: &mut ThreadRng, params: (Colors,f32,f32)) -> Colors {
+        let (pixels, outlier_prob, sigma) = params;
+        match self {
+            ColorLikelihood::Abc { dist, tolerance } => dist.random(rng, (pixels, *tolerance)),
+            ColorLikelihood::Plate { dist, foreground } => {
+                dist.random(rng, (pixels, foreground.clone(), outlier_prob, sigma))
+            }
+            ColorLikelihood::Perceptual { dist, chroma_sigma } => {
+                dist.random(rng, (pixels, outlier_prob, sigma, *chroma_sigma))
             }
+            ColorLikelihood::Pyramid(dist) => dist.random(rng, (pixels, outlier_prob, sigma))
         }
-        noisy_pixels
     }
 }
 
+thread_local! {
+    static RENDER_CACHE: RefCell<Option<ColorRenderCache>> = const { RefCell::new(None) };
+}
+
+/// restores the render cache that was in place before `with_render_cache`, even if its closure panics
+struct RenderCacheGuard(Option<ColorRenderCache>);
 
-/* dynamic generative functions */
+impl Drop for RenderCacheGuard {
+    fn drop(&mut self) {
+        RENDER_CACHE.with(|cache| *cache.borrow_mut() = self.0.take());
+    }
+}
+
+/// runs `f` with a new render cache for the perspective camera of `sphere_color_model`, `ball_model` and
+/// `shape_model`, leaving out changes of light below `tolerance` (see `light_radius`): the traces that they generate
+/// or update within `f` on this thread re-render only what changed since the scene rendered last, whichever trace
+/// that was, instead of in full
+pub fn with_render_cache<T>(tolerance: f32, f: impl FnOnce() -> T) -> T {
+    let mut cache = ColorRenderCache::new(Box::new(ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR)));
+    cache.tolerance = tolerance;
+    let _guard = RenderCacheGuard(RENDER_CACHE.with(|outer| outer.replace(Some(cache))));
+    f()
+}
+
+/// renders the colors of `scene` from pose `x` with `camera`, the perspective camera of the color models, through the
+/// render cache of the enclosing `with_render_cache` if there is one
+fn render_colors(camera: &ProjectiveCamera, x: Pose, scene: Scene, background_color: Color) -> Colors {
+    RENDER_CACHE.with(|cache| match cache.borrow_mut().as_mut() {
+        Some(cache) => cache.render(x, scene, background_color).0.clone(),
+        None => {
+            let mut pixels = vec![[0.0; 3]; AREA];
+            raytrace_colors(x, camera, &scene, background_color, &mut pixels);
+            pixels
+        }
+    })
+}
 
 dyngen!(
-pub fn grounded_depth_model() -> Depths {
+pub fn grounded_depth_model(obs: ObsParams) -> Depths {
     // camera pose
     let cam_y = uniform(0.5, 2.0) %= "cam_y";
     let cam_roll = normal(0.0, PI as f64/8.0) %= "cam_roll";
@@ -138,17 +1540,34 @@ pub fn grounded_depth_model() -> Depths {
         [0.0, 0.0, 0.0]
     );
 
+    // observation noise, inferred when it has a prior (a depth sensor has its own noise model)
+    let (outlier_prob, sigma) = match obs.noise_prior.filter(|_| obs.depth_sensor.is_none()) {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.depth_outlier_prob, obs.depth_sigma)
+    };
+
     // render
-    let proj = perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
     let mut pixels = vec![0.0; AREA];
-    raytrace_depths(x, proj, &vec![ground], &mut pixels);
-    noisy_depths(pixels.clone(), 0.1) %= "observation";
+    raytrace_depths(x, &camera, &vec![ground], &mut pixels);
+    if let Some(sensor) = obs.depth_sensor {
+        let likelihood = Tempered { dist: SensorDepths { mask: obs.mask.clone() }, inv_temp: obs.inv_temp };
+        likelihood(pixels.clone(), sensor) %= "observation";
+    } else {
+        let dist = PyramidDepths { noise: obs.noise, mask: obs.mask.clone(), level: obs.level };
+        let likelihood = Tempered { dist, inv_temp: obs.inv_temp };
+        likelihood(pixels.clone(), outlier_prob, sigma) %= "observation";
+    }
 
     pixels
 });
 
 dyngen!(
-pub fn sphere_color_model() -> Colors {
+pub fn sphere_color_model(obs: ObsParams) -> Colors {
     // camera pose
     let cam_y = uniform(0.5, 2.0) %= "cam_y";
     let cam_roll = normal(0.0, PI as f64/8.0) %= "cam_roll";
@@ -174,21 +1593,47 @@ pub fn sphere_color_model() -> Colors {
         [0.2, 1.0 - redness, redness]
     );
 
-    // render
-    let proj = perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
-    let mut pixels = vec![[0.0; 3]; AREA];
-    raytrace_colors(x, proj, &vec![ground, sphere], background_color, &mut pixels);
-    noisy_colors(pixels.clone(), 0.1) %= "observation";
+    // observation noise, inferred when it has a prior
+    let (outlier_prob, sigma) = match obs.noise_prior {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.outlier_prob, obs.sigma)
+    };
+
+    // render and observe, around the coverage of the sphere if against a background plate
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene = vec![ground, sphere];
+    let dist = ColorLikelihood::new(&obs, || raytrace_coverage(x, &camera, &scene, &[false, true]));
+    let pixels = render_colors(&camera, x, scene, background_color);
+    if obs.features != Features::Edges {
+        let likelihood = Tempered { dist, inv_temp: obs.inv_temp };
+        likelihood(pixels.clone(), outlier_prob, sigma) %= "observation";
+    }
+    if obs.features != Features::Pixels {
+        let likelihood = Tempered { dist: NoisyEdges { mask: obs.mask.clone() }, inv_temp: obs.inv_temp };
+        likelihood(edge_map(&pixels), obs.edge_noise) %= "edges";
+    }
 
     pixels
 });
 
 dyngen!(
-pub fn ball_model() -> Colors {
+pub fn ball_model(obs: ObsParams) -> Colors {
     // camera pose
     let cam_y = uniform(0.5, 2.0) %= "cam_y";
-    let cam_yaw = normal(0.0, PI as f64/8.0) %= "cam_yaw";
-    let x = vec3_euler_to_pose([0.0, cam_y as f32, 1.2], [cam_yaw as f32, 0.0, 0.0]);
+    let q = match obs.cam_rotation {
+        Some(kappa) => {
+            isotropic_so3(quat_id(), kappa) %= "cam_rotation"
+        }
+        None => {
+            let cam_yaw = normal(0.0, PI as f64/8.0) %= "cam_yaw";
+            euler_xyz([cam_yaw as f32, 0.0, 0.0])
+        }
+    };
+    let x = [0.0, cam_y as f32, 1.2, q[0], q[1], q[2], q[3]];
 
     // background
     let brightness = (uniform(0.75, 1.0) %= "ambient_brightness") as f32;
@@ -218,19 +1663,577 @@ pub fn ball_model() -> Colors {
         ball_c
     );
 
-    // render
-    let proj = perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
-    let mut pixels = vec![[0.0; 3]; AREA];
-    raytrace_colors(x, proj, &vec![table, ball], background_color, &mut pixels);
-    noisy_colors(pixels.clone(), 0.1) %= "observation";
+    // observation noise, inferred when it has a prior
+    let (outlier_prob, sigma) = match obs.noise_prior {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.outlier_prob, obs.sigma)
+    };
+
+    // render and observe, around the coverage of the ball if against a background plate
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene = vec![table, ball];
+    let dist = ColorLikelihood::new(&obs, || raytrace_coverage(x, &camera, &scene, &[false, true]));
+    let pixels = render_colors(&camera, x, scene, background_color);
+    if obs.features != Features::Edges {
+        let likelihood = Tempered { dist, inv_temp: obs.inv_temp };
+        likelihood(pixels.clone(), outlier_prob, sigma) %= "observation";
+    }
+    if obs.features != Features::Pixels {
+        let likelihood = Tempered { dist: NoisyEdges { mask: obs.mask.clone() }, inv_temp: obs.inv_temp };
+        likelihood(edge_map(&pixels), obs.edge_noise) %= "edges";
+    }
+
+    pixels
+});
+
+dyngen!(
+pub fn shape_model(obs: ObsParams) -> Colors {
+    // camera pose
+    let cam_y = uniform(0.5, 2.0) %= "cam_y";
+    let q = match obs.cam_rotation {
+        Some(kappa) => {
+            isotropic_so3(quat_id(), kappa) %= "cam_rotation"
+        }
+        None => {
+            let cam_yaw = normal(0.0, PI as f64/8.0) %= "cam_yaw";
+            euler_xyz([cam_yaw as f32, 0.0, 0.0])
+        }
+    };
+    let x = [0.0, cam_y as f32, 1.2, q[0], q[1], q[2], q[3]];
+
+    // background
+    let brightness = (uniform(0.75, 1.0) %= "ambient_brightness") as f32;
+    let global_c = vec3_scale(&[0.9, 1.0, 1.0], brightness);
+    let background_color = global_c;
+
+    // ground
+    let mut table_c = vec3_zero();
+    table_c[0] = (uniform(0.0, 1.0) %= "table_c0") as f32;
+    table_c[1] = (uniform(0.0, 1.0) %= "table_c1") as f32;
+    table_c[2] = (uniform(0.0, 1.0) %= "table_c2") as f32;
+    let table = (
+        Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>,
+        table_c
+    );
+
+    // object, whose class decides which size parameters it has; a constrained index of no class is impossible, and
+    // leaves the table bare
+    let class = ShapeClass::try_from(checked_categorical(vec![1./3., 1./3., 1./3.]) %= "shape").ok();
+    let u = (uniform(-1.0, 1.0) %= "object_u") as f32;
+    let v = (uniform(-1.0, 0.0) %= "object_v") as f32;
+    let mut object_c = vec3_zero();
+    object_c[0] = (uniform(0.25, 1.0) %= "object_c0") as f32;
+    object_c[1] = (uniform(0.25, 1.0) %= "object_c1") as f32;
+    object_c[2] = (uniform(0.25, 1.0) %= "object_c2") as f32;
+    let sizes = match class {
+        Some(ShapeClass::Sphere) => [uniform(0.3, 0.5) %= "sphere_radius"].to_vec(),
+        Some(ShapeClass::Cuboid) => [
+            uniform(0.2, 0.5) %= "cuboid_half_x",
+            uniform(0.2, 0.5) %= "cuboid_half_y",
+            uniform(0.2, 0.5) %= "cuboid_half_z",
+            uniform(-PI as f64/4.0, PI as f64/4.0) %= "cuboid_yaw"
+        ].to_vec(),
+        Some(ShapeClass::Cylinder) => {
+            [uniform(0.2, 0.5) %= "cylinder_radius", uniform(0.2, 0.5) %= "cylinder_half_height"].to_vec()
+        }
+        None => vec![]
+    };
+    let object = class.map(|class| (class.solid(u, v, &sizes), object_c));
+
+    // observation noise, inferred when it has a prior
+    let (outlier_prob, sigma) = match obs.noise_prior {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.outlier_prob, obs.sigma)
+    };
+
+    // render and observe, around the coverage of the object if against a background plate
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene = [table].into_iter().chain(object).collect::<Scene>();
+    let dist = ColorLikelihood::new(&obs, || raytrace_coverage(x, &camera, &scene, &[false, true][..scene.len()]));
+    let pixels = render_colors(&camera, x, scene, background_color);
+    if obs.features != Features::Edges {
+        let likelihood = Tempered { dist, inv_temp: obs.inv_temp };
+        likelihood(pixels.clone(), outlier_prob, sigma) %= "observation";
+    }
+    if obs.features != Features::Pixels {
+        let likelihood = Tempered { dist: NoisyEdges { mask: obs.mask.clone() }, inv_temp: obs.inv_temp };
+        likelihood(edge_map(&pixels), obs.edge_noise) %= "edges";
+    }
+
+    pixels
+});
+
+dyngen!(
+pub fn stereo_ball_model(obs: ObsParams, rig: Rig<ProjectiveCamera>) -> Vec<Colors> {
+    // a background plate is of a single view, so every view is scored against its render
+    let obs = ObsParams { background: None, ..obs };
+
+    // rig pose
+    let cam_y = uniform(0.5, 2.0) %= "cam_y";
+    let q = match obs.cam_rotation {
+        Some(kappa) => {
+            isotropic_so3(quat_id(), kappa) %= "cam_rotation"
+        }
+        None => {
+            let cam_yaw = normal(0.0, PI as f64/8.0) %= "cam_yaw";
+            euler_xyz([cam_yaw as f32, 0.0, 0.0])
+        }
+    };
+    let x = [0.0, cam_y as f32, 1.2, q[0], q[1], q[2], q[3]];
+
+    // background
+    let brightness = (uniform(0.75, 1.0) %= "ambient_brightness") as f32;
+    let global_c = vec3_scale(&[0.9, 1.0, 1.0], brightness);
+    let background_color = global_c;
+
+    // ground
+    let mut table_c = vec3_zero();
+    table_c[0] = (uniform(0.0, 1.0) %= "table_c0") as f32;
+    table_c[1] = (uniform(0.0, 1.0) %= "table_c1") as f32;
+    table_c[2] = (uniform(0.0, 1.0) %= "table_c2") as f32;
+    let table = (
+        Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>,
+        table_c
+    );
+
+    // ball
+    let u = (uniform(-1.0, 1.0) %= "ball_u") as f32;
+    let v = (uniform(-1.0, 0.0) %= "ball_v") as f32;
+    let mut ball_c = vec3_zero();
+    ball_c[0] = (uniform(0.25, 1.0) %= "ball_c0") as f32;
+    ball_c[1] = (uniform(0.25, 1.0) %= "ball_c1") as f32;
+    ball_c[2] = (uniform(0.25, 1.0) %= "ball_c2") as f32;
+    let ball_r = (uniform(0.3, 0.5) %= "ball_radius") as f32;
+    let ball = (
+        Box::new(Sphere { center: [u, ball_r, v], radius: ball_r }) as Box<dyn Solid>,
+        ball_c
+    );
+
+    // observation noise, inferred when it has a prior and shared by the views
+    let (outlier_prob, sigma) = match obs.noise_prior {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.outlier_prob, obs.sigma)
+    };
+
+    // render each view and observe them jointly
+    let mut views = vec![vec![[0.0; 3]; AREA]; rig.len()];
+    raytrace_rig_colors(x, &rig, &vec![table, ball], background_color, &mut views);
+    for (i, pixels) in views.iter().enumerate() {
+        if obs.features != Features::Edges {
+            let likelihood = Tempered { dist: ColorLikelihood::new(&obs, || Mask::All), inv_temp: obs.inv_temp };
+            likelihood(pixels.clone(), outlier_prob, sigma) %= &format!("observation_{i}");
+        }
+        if obs.features != Features::Pixels {
+            let likelihood = Tempered { dist: NoisyEdges { mask: obs.mask.clone() }, inv_temp: obs.inv_temp };
+            likelihood(edge_map(pixels), obs.edge_noise) %= &format!("edges_{i}");
+        }
+    }
+
+    views
+});
+
+dyngen!(
+pub fn rgbd_ball_model(obs: ObsParams) -> Rgbd {
+    // camera pose
+    let cam_y = uniform(0.5, 2.0) %= "cam_y";
+    let q = match obs.cam_rotation {
+        Some(kappa) => {
+            isotropic_so3(quat_id(), kappa) %= "cam_rotation"
+        }
+        None => {
+            let cam_yaw = normal(0.0, PI as f64/8.0) %= "cam_yaw";
+            euler_xyz([cam_yaw as f32, 0.0, 0.0])
+        }
+    };
+    let x = [0.0, cam_y as f32, 1.2, q[0], q[1], q[2], q[3]];
+
+    // background
+    let brightness = (uniform(0.75, 1.0) %= "ambient_brightness") as f32;
+    let global_c = vec3_scale(&[0.9, 1.0, 1.0], brightness);
+    let background_color = global_c;
+
+    // ground
+    let mut table_c = vec3_zero();
+    table_c[0] = (uniform(0.0, 1.0) %= "table_c0") as f32;
+    table_c[1] = (uniform(0.0, 1.0) %= "table_c1") as f32;
+    table_c[2] = (uniform(0.0, 1.0) %= "table_c2") as f32;
+    let table = (
+        Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>,
+        table_c
+    );
+
+    // ball
+    let u = (uniform(-1.0, 1.0) %= "ball_u") as f32;
+    let v = (uniform(-1.0, 0.0) %= "ball_v") as f32;
+    let mut ball_c = vec3_zero();
+    ball_c[0] = (uniform(0.25, 1.0) %= "ball_c0") as f32;
+    ball_c[1] = (uniform(0.25, 1.0) %= "ball_c1") as f32;
+    ball_c[2] = (uniform(0.25, 1.0) %= "ball_c2") as f32;
+    let ball_r = (uniform(0.3, 0.5) %= "ball_radius") as f32;
+    let ball = (
+        Box::new(Sphere { center: [u, ball_r, v], radius: ball_r }) as Box<dyn Solid>,
+        ball_c
+    );
+
+    // color noise, inferred when it has a prior
+    let (outlier_prob, sigma) = match obs.noise_prior {
+        Some(prior) => {
+            let outlier_prob = uniform(0.0, prior.max_outlier_prob) %= "outlier_prob";
+            let log_sigma = normal(prior.log_sigma.0, prior.log_sigma.1) %= "log_pixel_noise";
+            (outlier_prob as f32, log_sigma.exp() as f32)
+        }
+        None => (obs.outlier_prob, obs.sigma)
+    };
+
+    // render color and depth of the same scene and observe them jointly
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene = vec![table, ball];
+    let mut colors = vec![[0.0; 3]; AREA];
+    let mut depths = vec![0.0; AREA];
+    raytrace_colors(x, &camera, &scene, background_color, &mut colors);
+    raytrace_depths(x, &camera, &scene, &mut depths);
+    let pixels = (colors, depths);
+    let dist = NoisyRgbd { noise: obs.noise, mask: obs.mask.clone(), level: obs.level, depth_sensor: obs.depth_sensor };
+    let likelihood = Tempered { dist, inv_temp: obs.inv_temp };
+    likelihood(pixels.clone(), outlier_prob, sigma, obs.depth_outlier_prob, obs.depth_sigma) %= "observation";
 
     pixels
 });
 
 dyngen!(
-pub fn gaussian_drift(trace: Weak<DynTrace<(),Colors>>, mask: Vec<&str>, stdev: f64) {
+pub fn gaussian_drift(trace: Weak<DynTrace<ObsParams,Colors>>, mask: Vec<&str>, stdev: f64) {
     let trace = trace.upgrade().unwrap();
     for addr in mask.iter() {
         normal(trace.data.read::<f64>(addr), stdev) %= addr;
     }
-});
\ No newline at end of file
+});
+
+dyngen!(
+pub fn rotation_drift(trace: Weak<DynTrace<ObsParams,Colors>>, mask: Vec<&str>, stdev: f32) {
+    let trace = trace.upgrade().unwrap();
+    for addr in mask.iter() {
+        isotropic_so3(trace.data.read::<Quat>(addr), IsotropicSO3::concentration(stdev)) %= addr;
+    }
+});
+
+dyngen!(
+pub fn pose_drift(trace: Weak<DynTrace<ObsParams,Colors>>, mask: Vec<&str>, stdev: f32, rotation_stdev: f32) {
+    let trace = trace.upgrade().unwrap();
+    for addr in mask.iter() {
+        gaussian_pose(trace.data.read::<Pose>(addr), stdev, IsotropicSO3::concentration(rotation_stdev)) %= addr;
+    }
+});
+
+dyngen!(
+pub fn shape_switch(trace: Weak<DynTrace<ObsParams,Colors>>, stdev: f64) {
+    // switches `shape_model` to another class at the same position, with about the same width and height
+    let trace = trace.upgrade().unwrap();
+    let Ok(class) = ShapeClass::try_from(trace.data.read::<i64>("shape")) else {
+        return;
+    };
+    let (half_width, half_height) = match class {
+        ShapeClass::Sphere => {
+            let radius = trace.data.read::<f64>("sphere_radius");
+            (radius, radius)
+        }
+        ShapeClass::Cuboid => {
+            let half_x = trace.data.read::<f64>("cuboid_half_x");
+            let half_z = trace.data.read::<f64>("cuboid_half_z");
+            (0.5 * (half_x + half_z), trace.data.read::<f64>("cuboid_half_y"))
+        }
+        ShapeClass::Cylinder => (trace.data.read::<f64>("cylinder_radius"), trace.data.read::<f64>("cylinder_half_height"))
+    };
+    let mut probs = vec![0.5; 3];
+    probs[class.index() as usize] = 0.;
+    // proposed within the bounds of the prior of `shape_model`, so that no proposal is rejected outright
+    match ShapeClass::ALL[(categorical(probs) %= "shape") as usize] {
+        ShapeClass::Sphere => {
+            truncated_normal(0.5 * (half_width + half_height), stdev, 0.3, 0.5) %= "sphere_radius";
+        }
+        ShapeClass::Cuboid => {
+            truncated_normal(half_width, stdev, 0.2, 0.5) %= "cuboid_half_x";
+            truncated_normal(half_height, stdev, 0.2, 0.5) %= "cuboid_half_y";
+            truncated_normal(half_width, stdev, 0.2, 0.5) %= "cuboid_half_z";
+            uniform(-PI as f64/4.0, PI as f64/4.0) %= "cuboid_yaw";
+        }
+        ShapeClass::Cylinder => {
+            truncated_normal(half_width, stdev, 0.2, 0.5) %= "cylinder_radius";
+            truncated_normal(half_height, stdev, 0.2, 0.5) %= "cylinder_half_height";
+        }
+    }
+});
+
+/* annealing */
+
+/// tempered distribution type: scales the log density of `dist` by the inverse temperature `inv_temp`, which
+/// flattens it for `inv_temp < 1`. The result is unnormalized, and samples are drawn from `dist` itself.
+pub struct Tempered<D> { pub dist: D, pub inv_temp: f64 }
+
+impl<T, P, D: Distribution<T,P>> Distribution<T,P> for Tempered<D> {
+    fn logpdf(&self, x: &T, params: P) -> f64 {
+        if self.inv_temp == 0. {
+            return 0.;
+        }
+        self.inv_temp * self.dist.logpdf(x, params)
+    }
+
+    fn random(&self, rng: &mut ThreadRng, params: P) -> T {
+        self.dist.random(rng, params)
+    }
+}
+
+/// an annealing schedule of inverse temperatures
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    /// `steps` steps from `from` to `to` in equal increments
+    Linear { from: f64, to: f64, steps: usize },
+    /// `steps` steps from `from` to `to` in equal ratios
+    Geometric { from: f64, to: f64, steps: usize },
+    /// from `from` to `to`, multiplying by `1 + rate` after each step while the running acceptance rate is at
+    /// least `target_accept`, and by less the further it falls below (but by at least `1 + rate / 10`)
+    Adaptive { from: f64, to: f64, rate: f64, target_accept: f64 }
+}
+
+/// the state of a `Schedule` in an inference loop
+#[derive(Clone, Debug)]
+pub struct Annealing {
+    pub schedule: Schedule,
+    step: usize,
+    inv_temp: f64,
+    accept_rate: f64,
+    done: bool
+}
+
+impl Annealing {
+    /// panics unless the schedule rises from `from` to `to`, where the geometric and adaptive schedules, which scale
+    /// the inverse temperature, need `from > 0`, and the adaptive one also a positive `rate` and `target_accept`
+    pub fn new(schedule: Schedule) -> Self {
+        match schedule {
+            Schedule::Linear { from, to, .. } => {
+                assert!(0. <= from && from <= to, "linear schedule from {from} to {to}");
+            }
+            Schedule::Geometric { from, to, .. } => {
+                assert!(0. < from && from <= to, "geometric schedule from {from} to {to}");
+            }
+            Schedule::Adaptive { from, to, rate, target_accept } => {
+                assert!(0. < from && from <= to, "adaptive schedule from {from} to {to}");
+                assert!(rate > 0. && target_accept > 0., "adaptive schedule at rate {rate} to acceptance {target_accept}");
+            }
+        }
+        // only the adaptive schedule follows the running acceptance rate, which starts out on target
+        let (inv_temp, accept_rate, done) = match schedule {
+            Schedule::Linear { from, steps, .. } | Schedule::Geometric { from, steps, .. } => (from, 1., steps == 0),
+            Schedule::Adaptive { from, target_accept, .. } => (from, target_accept, false)
+        };
+        Annealing { schedule, step: 0, inv_temp, accept_rate, done }
+    }
+
+    /// the inverse temperature of the current step, or `None` once the schedule is over
+    pub fn inv_temp(&self) -> Option<f64> {
+        if self.done { None } else { Some(self.inv_temp) }
+    }
+
+    /// moves to the next step, after the current one `accepted` its proposal or not
+    pub fn advance(&mut self, accepted: bool) {
+        self.step += 1;
+        self.accept_rate = 0.9 * self.accept_rate + if accepted { 0.1 } else { 0. };
+        let t = self.step as f64;
+        match self.schedule {
+            Schedule::Linear { from, to, steps } => {
+                self.inv_temp = from + (to - from) * t / steps.saturating_sub(1).max(1) as f64;
+                self.done = self.step >= steps;
+            }
+            Schedule::Geometric { from, to, steps } => {
+                self.inv_temp = from * (to / from).powf(t / steps.saturating_sub(1).max(1) as f64);
+                self.done = self.step >= steps;
+            }
+            Schedule::Adaptive { to, rate, target_accept, .. } => {
+                self.done = self.inv_temp >= to;
+                let progress = (self.accept_rate / target_accept).clamp(0.1, 1.);
+                self.inv_temp = (self.inv_temp * (1. + rate * progress)).min(to);
+            }
+        }
+    }
+}
+
+/// rescores a trace with new observation settings
+pub fn rescore<R: Clone + 'static>(
+    model: &DynGenFn<ObsParams,R>,
+    trace: DynTrace<ObsParams,R>,
+    obs: ObsParams
+) -> DynTrace<ObsParams,R> {
+    let mut trace = model.update(trace, obs.clone(), ArgDiff::Unknown, DynTrie::new()).0;
+    // `update` rescores the trace but leaves its old arguments in place
+    trace.args = obs;
+    trace
+}
+
+/// simulated annealing: runs `step` at each inverse temperature of `schedule` in turn, where `step` returns the
+/// new trace and whether its proposal was accepted, rescoring the trace whenever the temperature changes. The
+/// result is rescored with the untempered likelihood.
+pub fn anneal<R: Clone + 'static>(
+    model: &DynGenFn<ObsParams,R>,
+    mut trace: DynTrace<ObsParams,R>,
+    schedule: Schedule,
+    mut step: impl FnMut(DynTrace<ObsParams,R>) -> (DynTrace<ObsParams,R>, bool)
+) -> DynTrace<ObsParams,R> {
+    let mut annealing = Annealing::new(schedule);
+    while let Some(inv_temp) = annealing.inv_temp() {
+        if inv_temp != trace.args.inv_temp {
+            let obs = ObsParams { inv_temp, ..trace.args.clone() };
+            trace = rescore(model, trace, obs);
+        }
+        let (new_trace, accepted) = step(trace);
+        trace = new_trace;
+        annealing.advance(accepted);
+    }
+    if trace.args.inv_temp == 1.0 {
+        return trace;
+    }
+    let obs = ObsParams { inv_temp: 1.0, ..trace.args.clone() };
+    rescore(model, trace, obs)
+}
+
+/// coarse-to-fine inference: runs `iters` steps of `step` with the observation scored at each pyramid level of
+/// `levels` in turn, e.g. `[3, 2, 1, 0]`, so that early steps explore a flat likelihood and later ones refine it
+pub fn coarse_to_fine<R: Clone + 'static>(
+    model: &DynGenFn<ObsParams,R>,
+    mut trace: DynTrace<ObsParams,R>,
+    levels: &[usize],
+    iters: usize,
+    mut step: impl FnMut(DynTrace<ObsParams,R>) -> DynTrace<ObsParams,R>
+) -> DynTrace<ObsParams,R> {
+    for &level in levels {
+        let obs = ObsParams { level, ..trace.args.clone() };
+        trace = rescore(model, trace, obs);
+        for _ in 0..iters {
+            trace = step(trace);
+        }
+    }
+    trace
+}
+
+
+/* posterior summaries */
+
+/// the inferred `(outlier_prob, sigma)` of a trace whose model has a `NoisePrior`
+pub fn inferred_noise<R: Clone + 'static>(trace: &DynTrace<ObsParams,R>) -> Option<(f32, f32)> {
+    trace.data.search("log_pixel_noise")?;
+    let outlier_prob = trace.data.read::<f64>("outlier_prob");
+    let log_sigma = trace.data.read::<f64>("log_pixel_noise");
+    Some((outlier_prob as f32, log_sigma.exp() as f32))
+}
+
+/// per-pixel log-likelihoods of the observation of a depth model trace at full resolution, e.g. of
+/// `grounded_depth_model`, which sum to its untempered log-likelihood
+pub fn depth_log_likelihood_map(trace: &DynTrace<ObsParams,Depths>) -> Vec<f64> {
+    let obs = &trace.args;
+    let observation = trace.data.read::<Depths>("observation");
+    let pixels = trace.retv.clone().unwrap();
+    if let Some(sensor) = obs.depth_sensor {
+        return SensorDepths { mask: obs.mask.clone() }.log_likelihood_map(&observation, (pixels, sensor));
+    }
+    let (outlier_prob, sigma) = inferred_noise(trace).unwrap_or((obs.depth_outlier_prob, obs.depth_sigma));
+    let dist = PyramidDepths { noise: obs.noise, mask: obs.mask.clone(), level: obs.level };
+    dist.log_likelihood_map(&observation, (pixels, outlier_prob, sigma))
+}
+
+/// the coverage of the foreground object of a trace of `sphere_color_model`, `ball_model` or `shape_model`, told
+/// apart by their addresses, recomputed from the camera pose and geometry that it traced
+fn traced_coverage(trace: &DynTrace<ObsParams,Colors>) -> Mask {
+    let data = &trace.data;
+    let read = |addr: &str| data.read::<f64>(addr) as f32;
+    let x = match data.search("cam_roll") {
+        Some(_) => vec3_euler_to_pose([0.0, read("cam_y"), 1.2], [0.0, 0.0, read("cam_roll")]),
+        None => {
+            let q = match data.search("cam_rotation") {
+                Some(_) => data.read::<Quat>("cam_rotation"),
+                None => euler_xyz([read("cam_yaw"), 0.0, 0.0])
+            };
+            [0.0, read("cam_y"), 1.2, q[0], q[1], q[2], q[3]]
+        }
+    };
+    let object: Option<Box<dyn Solid>> = if data.search("sphere_u").is_some() {
+        Some(Box::new(Sphere { center: [read("sphere_u"), 0.5, read("sphere_v")], radius: 0.5 }))
+    } else if data.search("ball_u").is_some() {
+        let radius = read("ball_radius");
+        Some(Box::new(Sphere { center: [read("ball_u"), radius, read("ball_v")], radius }))
+    } else {
+        ShapeClass::try_from(data.read::<i64>("shape")).ok().map(|class| {
+            let sizes = class.size_addrs().iter().map(|addr| data.read::<f64>(addr)).collect::<Vec<_>>();
+            class.solid(read("object_u"), read("object_v"), &sizes)
+        })
+    };
+    let plane = Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>;
+    let scene = [plane].into_iter().chain(object).map(|solid| (solid, vec3_zero())).collect::<Scene>();
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    raytrace_coverage(x, &camera, &scene, &[false, true][..scene.len()])
+}
+
+/// per-pixel log-likelihoods of the observed features of a color model trace at full resolution, e.g. of
+/// `ball_model`, which sum to its untempered log-likelihood, or `None` if they don't split into pixels (summary
+/// statistics)
+pub fn color_log_likelihood_map(trace: &DynTrace<ObsParams,Colors>) -> Option<Vec<f64>> {
+    let obs = &trace.args;
+    let pixels = trace.retv.clone().unwrap();
+    let mut map = vec![0.; AREA];
+    if obs.features != Features::Edges {
+        let observation = trace.data.read::<Colors>("observation");
+        let (outlier_prob, sigma) = inferred_noise(trace).unwrap_or((obs.outlier_prob, obs.sigma));
+        let dist = ColorLikelihood::new(obs, || traced_coverage(trace));
+        let pixel_ws = dist.log_likelihood_map(&observation, (pixels.clone(), outlier_prob, sigma))?;
+        map.iter_mut().zip(pixel_ws).for_each(|(w, w_pixel)| *w += w_pixel);
+    }
+    if obs.features != Features::Pixels {
+        let edges = trace.data.read::<Edges>("edges");
+        let edge_ws = NoisyEdges { mask: obs.mask.clone() }.log_likelihood_map(&edges, (edge_map(&pixels), obs.edge_noise));
+        map.iter_mut().zip(edge_ws).for_each(|(w, w_edge)| *w += w_edge);
+    }
+    Some(map)
+}
+
+/// per-pixel differences `a - b` of two log-likelihood maps, e.g. of a proposed and the current trace,
+/// taking pixels that are impossible under both as unchanged
+pub fn log_likelihood_diff(a: &[f64], b: &[f64]) -> Vec<f64> {
+    a.iter().zip(b.iter()).map(|(a, b)| if a == b { 0. } else { a - b }).collect()
+}
+
+/// per-pixel outlier probabilities of the observation of a depth model trace, e.g. of `grounded_depth_model`
+pub fn depth_outlier_map(trace: &DynTrace<ObsParams,Depths>) -> Depths {
+    let obs = &trace.args;
+    let observation = trace.data.read::<Depths>("observation");
+    let pixels = trace.retv.clone().unwrap();
+    if let Some(sensor) = obs.depth_sensor {
+        return observation.iter().zip(sensor.pixel_params(&pixels))
+            .map(|(x, params)| sensor_pixel.outlier_posterior(x, params) as f32)
+            .collect();
+    }
+    let (outlier_prob, sigma) = inferred_noise(trace).unwrap_or((obs.depth_outlier_prob, obs.depth_sigma));
+    let dist = PyramidDepths { noise: obs.noise, mask: obs.mask.clone(), level: obs.level };
+    dist.outlier_posteriors(&observation, (pixels, outlier_prob, sigma))
+}
+
+/// per-pixel outlier probabilities of the observed pixels of a color model trace, e.g. of `ball_model`, or `None` if
+/// it observes no pixels or they have no outlier component (summary statistics)
+pub fn color_outlier_map(trace: &DynTrace<ObsParams,Colors>) -> Option<Depths> {
+    let obs = &trace.args;
+    if obs.features == Features::Edges {
+        return None;
+    }
+    let observation = trace.data.read::<Colors>("observation");
+    let pixels = trace.retv.clone().unwrap();
+    let (outlier_prob, sigma) = inferred_noise(trace).unwrap_or((obs.outlier_prob, obs.sigma));
+    ColorLikelihood::new(obs, || traced_coverage(trace)).outlier_posteriors(&observation, (pixels, outlier_prob, sigma))
+}
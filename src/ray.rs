@@ -4,126 +4,543 @@ use modppl::prelude::*;
 use crate::types::*;
 use crate::linear::*;
 use crate::config::*;
+use crate::geometry::*;
 
 
-/* cpu ray tracers */
+/* cameras */
+
+/// a camera model relating camera-space points (looking down -z, +y up) to
+/// window coordinates (in pixels, origin at the bottom left of the viewport)
+pub trait Camera {
+    /// returns the window coordinates of a camera-space point, if it can be imaged
+    fn project(&self, p: Vec3) -> Option<Vec2>;
+    /// returns the camera-space ray (origin, unit direction) through window coordinates `uv`, if any
+    fn unproject(&self, uv: Vec2) -> Option<(Vec3,Vec3)>;
+
+    /// returns true if the camera images straight lines as straight lines, so that convex solids in front of it
+    /// image within the convex hull of the images of their corners
+    fn preserves_lines(&self) -> bool {
+        false
+    }
+}
+
+impl<C: Camera + ?Sized> Camera for Box<C> {
+    fn project(&self, p: Vec3) -> Option<Vec2> {
+        self.as_ref().project(p)
+    }
+
+    fn unproject(&self, uv: Vec2) -> Option<(Vec3,Vec3)> {
+        self.as_ref().unproject(uv)
+    }
+
+    fn preserves_lines(&self) -> bool {
+        self.as_ref().preserves_lines()
+    }
+}
+
+/// Brown-Conrady (radial-tangential) lens distortion coefficients, acting on
+/// normalized image coordinates (x/-z, y/-z for a pinhole camera)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Distortion {
+    pub k1: f32,
+    pub k2: f32,
+    pub k3: f32,
+    pub p1: f32,
+    pub p2: f32
+}
+
+impl Distortion {
+    /// maps ideal normalized coordinates to distorted (sensor) coordinates
+    pub fn distort(&self, p: Vec2) -> Vec2 {
+        let [x, y] = p;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        [
+            x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+            y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y
+        ]
+    }
+
+    /// inverts `distort` by fixed-point iteration
+    pub fn undistort(&self, p: Vec2) -> Vec2 {
+        let [xd, yd] = p;
+        let (mut x, mut y) = (xd, yd);
+        for _ in 0..20 {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+            let dx = 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x);
+            let dy = self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y;
+            let (x_next, y_next) = ((xd - dx) / radial, (yd - dy) / radial);
+            let converged = (x_next - x).abs() < 1e-7 && (y_next - y).abs() < 1e-7;
+            (x, y) = (x_next, y_next);
+            if converged { break; }
+        }
+        [x, y]
+    }
+}
+
+/// perspective or orthographic camera given by an OpenGL-style projection matrix,
+/// with optional lens distortion applied in its normalized image plane
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectiveCamera {
+    pub proj: Mat4,
+    pub distortion: Distortion,
+    i_proj: Mat4
+}
+
+impl ProjectiveCamera {
+    pub fn new(proj: Mat4) -> Self {
+        ProjectiveCamera { proj, distortion: Distortion::default(), i_proj: mat4_inv(proj) }
+    }
+
+    pub fn perspective(fovy: f32, aspect_ratio: f32, nearz: f32, farz: f32) -> Self {
+        Self::new(perspective(fovy, aspect_ratio, nearz, farz))
+    }
+
+    /// pinhole camera from calibrated intrinsics (in pixels, with the image origin at the
+    /// top left as in OpenCV) and distortion coefficients
+    pub fn from_intrinsics(fx: f32, fy: f32, cx: f32, cy: f32, distortion: Distortion, nearz: f32, farz: f32) -> Self {
+        let mut proj = perspective(PI/2.0, 1.0, nearz, farz);
+        proj[0][0] = 2.0 * fx / W as f32;
+        proj[1][1] = 2.0 * fy / H as f32;
+        proj[2][0] = 1.0 - 2.0 * cx / W as f32;
+        proj[2][1] = 2.0 * cy / H as f32 - 1.0;
+        ProjectiveCamera { distortion, ..Self::new(proj) }
+    }
+
+    /// orthographic camera imaging a `width` x `height` window of the scene (in world units)
+    pub fn orthographic(width: f32, height: f32, nearz: f32, farz: f32) -> Self {
+        Self::new(ortho(-0.5*width, 0.5*width, -0.5*height, 0.5*height, nearz, farz))
+    }
+}
+
+impl ProjectiveCamera {
+    /// maps window coordinates between the ideal and distorted image
+    fn warp(&self, uv: Vec2, f: impl Fn(&Distortion, Vec2) -> Vec2) -> Vec2 {
+        if self.distortion == Distortion::default() {
+            return uv;
+        }
+        let scale = [self.proj[0][0], self.proj[1][1]];
+        let center = [self.proj[3][0] - self.proj[2][0], self.proj[3][1] - self.proj[2][1]];
+        let mut p = [0.0; 2];
+        for i in 0..=1 {
+            let ndc = 2.0 * (uv[i] - VIEWPORT[i]) / VIEWPORT[i + 2] - 1.0;
+            p[i] = (ndc - center[i]) / scale[i];
+        }
+        let p = f(&self.distortion, p);
+        let mut out = [0.0; 2];
+        for i in 0..=1 {
+            let ndc = p[i] * scale[i] + center[i];
+            out[i] = 0.5 * (ndc + 1.0) * VIEWPORT[i + 2] + VIEWPORT[i];
+        }
+        out
+    }
+}
 
-struct UniformS2 { }
-const uniform_s2: UniformS2 = UniformS2 { };
+impl Camera for ProjectiveCamera {
+    fn project(&self, p: Vec3) -> Option<Vec2> {
+        let w = mat4_mulv(self.proj, [p[0], p[1], p[2], 1.0])[3];
+        if w <= 0.0 {
+            return None;
+        }
+        let q = project(p, self.proj, VIEWPORT);
+        Some(self.warp([q[0], q[1]], Distortion::distort))
+    }
 
-impl Distribution<Vec3,()> for UniformS2 {
-    fn logpdf(&self, _: &Vec3, _: ()) -> f64 {
-        panic!("not implemented!");
+    fn unproject(&self, uv: Vec2) -> Option<(Vec3,Vec3)> {
+        let uv = self.warp(uv, Distortion::undistort);
+        let near_p = unproject_inv([uv[0], uv[1], -1.0], self.i_proj, VIEWPORT);
+        let far_p  = unproject_inv([uv[0], uv[1],  1.0], self.i_proj, VIEWPORT);
+        let mut ray_dir = vec3_sub(far_p, near_p);
+        vec3_normalize(&mut ray_dir);
+        Some((near_p, ray_dir))
     }
-    
-    fn random(&self, rng: &mut ThreadRng, _: ()) -> Vec3 {
-        let z = 2.0 * u01(rng) as f32 - 1.0;
-        let theta = 2.0 * PI * u01(rng) as f32;
-        let r = (1.0 - z * z).sqrt();
-        [r * theta.cos(), r * theta.sin(), z]
+
+    fn preserves_lines(&self) -> bool {
+        self.distortion == Distortion::default()
     }
 }
 
+/// fisheye lens models, relating the angle `theta` off the optical axis to the image radius `r`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeModel {
+    /// r = f * theta
+    Equidistant,
+    /// r = 2f * sin(theta / 2)
+    Equisolid
+}
+
+/// fisheye camera with its optical axis through the center of the viewport
+pub struct FisheyeCamera {
+    pub model: FisheyeModel,
+    pub focal: f32,  // in pixels
+    pub near: f32
+}
+
+impl FisheyeCamera {
+    /// fisheye camera whose horizontal field of view `fovx` spans the viewport width
+    pub fn new(model: FisheyeModel, fovx: f32, nearz: f32) -> Self {
+        let r = 0.5 * W as f32;
+        let focal = match model {
+            FisheyeModel::Equidistant => r / (0.5 * fovx),
+            FisheyeModel::Equisolid => r / (2.0 * (0.25 * fovx).sin())
+        };
+        FisheyeCamera { model, focal, near: nearz }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn project(&self, p: Vec3) -> Option<Vec2> {
+        let len = vec3_norm(&p);
+        if len < self.near {
+            return None;
+        }
+        let theta = (-p[2] / len).clamp(-1.0, 1.0).acos();
+        let r = match self.model {
+            FisheyeModel::Equidistant => self.focal * theta,
+            FisheyeModel::Equisolid => 2.0 * self.focal * (0.5 * theta).sin()
+        };
+        let phi = p[1].atan2(p[0]);
+        Some([0.5 * W as f32 + r * phi.cos(), 0.5 * H as f32 + r * phi.sin()])
+    }
+
+    fn unproject(&self, uv: Vec2) -> Option<(Vec3,Vec3)> {
+        let du = uv[0] - 0.5 * W as f32;
+        let dv = uv[1] - 0.5 * H as f32;
+        let r = (du * du + dv * dv).sqrt();
+        let theta = match self.model {
+            FisheyeModel::Equidistant => r / self.focal,
+            FisheyeModel::Equisolid => {
+                let s = r / (2.0 * self.focal);
+                if s > 1.0 { return None; }
+                2.0 * s.asin()
+            }
+        };
+        if theta > PI {
+            return None;
+        }
+        let (cos_phi, sin_phi) = if r > 0.0 { (du / r, dv / r) } else { (1.0, 0.0) };
+        let ray_dir = [theta.sin() * cos_phi, theta.sin() * sin_phi, -theta.cos()];
+        Some((vec3_scale(&ray_dir, self.near), ray_dir))
+    }
+}
+
+/// a set of cameras, each with its pose relative to the rig; a rig of one camera type, such as the `ProjectiveCamera`s
+/// of `stereo_ball_model`, can be cloned and compared
+pub type Rig<C = Box<dyn Camera>> = Vec<(Pose, C)>;
+
+/// returns a horizontal stereo pair (left, right) of perspective cameras `baseline` apart
+pub fn stereo_rig(baseline: f32, fovy: f32, nearz: f32, farz: f32) -> Rig<ProjectiveCamera> {
+    [-0.5 * baseline, 0.5 * baseline].iter().map(|&dx| {
+        let p = [dx, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        (p, ProjectiveCamera::perspective(fovy, W as f32/H as f32, nearz, farz))
+    }).collect()
+}
+
+/// returns the world-space ray through window coordinates `uv` of a camera with isometry `iso`
+fn camera_ray(camera: &dyn Camera, iso: Mat4, uv: Vec2) -> Option<(Vec3,Vec3)> {
+    let (o, d) = camera.unproject(uv)?;
+    let o = mat4_mulv(iso, [o[0], o[1], o[2], 1.0]);
+    let d = mat4_mulv(iso, [d[0], d[1], d[2], 0.0]);
+    Some(([o[0], o[1], o[2]], [d[0], d[1], d[2]]))
+}
+
+
+/* cpu ray tracers */
+
 /// returns a depth raytace
-pub fn raytrace_depths(x: Pose, proj: Mat4, scene: &Scene, out: &mut Depths) {
+pub fn raytrace_depths(x: Pose, camera: &dyn Camera, scene: &Scene, out: &mut Depths) {
     let iso = pose_to_mat4(x);
-    let i_proj = mat4_inv(proj);
-    let i = mat4_mul(iso, i_proj);
+
+    // rays are traced one row of pixels at a time
+    let mut rays = RayPacket::with_capacity(W);
+    let mut pixels = Vec::with_capacity(W);
+    let mut distances = vec![f32::INFINITY; W];
+    let mut nearest = vec![f32::INFINITY; W];
 
     for y in 0..H {
+        rays.clear();
+        pixels.clear();
         for x in 0..W {
-            let vp = [ 0.0, 0.0, W as f32, H as f32 ];
-            let near_pw = unproject_inv([x as f32, (H - y) as f32, -1.0], i, vp);
-            let far_pw  = unproject_inv([x as f32, (H - y) as f32,  1.0], i, vp);
-
-            let ray_origin = near_pw;
-            let mut ray_dir = vec3_sub(far_pw, near_pw);
-            vec3_normalize(&mut ray_dir);
-
-            for s in scene.iter() {
-                if let Some(d) = s.0.ray_intersect(ray_origin, ray_dir) {
-                    let lm = if NEAR <= d && d <= FAR {
-                        1.0 - (d - NEAR) / (FAR - NEAR)
-                    } else {
-                        0.0
-                    };
-                    out[y * W + x] = lm;
+            if let Some((ray_origin, ray_dir)) = camera_ray(camera, iso, [x as f32, (H - y) as f32]) {
+                rays.push(ray_origin, ray_dir);
+                pixels.push(y * W + x);
+            }
+        }
+
+        // each pixel sees the nearest of the solids its ray hits
+        nearest.fill(f32::INFINITY);
+        for s in scene.iter() {
+            s.0.ray_intersect_packet(&rays, &mut distances);
+            for (n, &d) in nearest.iter_mut().zip(distances.iter()).take(rays.len()) {
+                *n = n.min(d);
+            }
+        }
+        for (&i, &d) in pixels.iter().zip(nearest.iter()) {
+            if d.is_finite() {
+                let lm = if NEAR <= d && d <= FAR {
+                    1.0 - (d - NEAR) / (FAR - NEAR)
+                } else {
+                    0.0
+                };
+                out[i] = lm;
+            }
+        }
+    }
+}
+
+/// returns the pixels whose primary ray first hits one of the solids of `scene` flagged in `foreground`
+pub fn raytrace_coverage(x: Pose, camera: &dyn Camera, scene: &Scene, foreground: &[bool]) -> Mask {
+    assert_eq!(scene.len(), foreground.len());
+    let iso = pose_to_mat4(x);
+
+    let mut rays = RayPacket::with_capacity(W);
+    let mut pixels = Vec::with_capacity(W);
+    let mut distances = vec![f32::INFINITY; W];
+    let mut nearest = vec![f32::INFINITY; W];
+    let mut covered = vec![false; AREA];
+
+    for y in 0..H {
+        rays.clear();
+        pixels.clear();
+        for x in 0..W {
+            if let Some((ray_origin, ray_dir)) = camera_ray(camera, iso, [x as f32, (H - y) as f32]) {
+                rays.push(ray_origin, ray_dir);
+                pixels.push(y * W + x);
+            }
+        }
+
+        nearest.fill(f32::INFINITY);
+        for (s, &is_foreground) in scene.iter().zip(foreground.iter()) {
+            s.0.ray_intersect_packet(&rays, &mut distances);
+            for (j, &i) in pixels.iter().enumerate() {
+                if distances[j] < nearest[j] {
+                    nearest[j] = distances[j];
+                    covered[i] = is_foreground;
                 }
             }
         }
     }
+    Mask::Pixels(Arc::new(covered))
 }
 
 /// returns a color raytrace with diffuse (Lambertian) reflection and global illumination
-pub fn raytrace_colors(x: Pose, proj: Mat4, scene: &Scene, background_color: Color, out: &mut Colors) {
+pub fn raytrace_colors(x: Pose, camera: &dyn Camera, scene: &Scene, background_color: Color, out: &mut Colors) {
+    raytrace_colors_rect(x, camera, scene, background_color, Rect::full(), out);
+}
+
+/// as `raytrace_colors`, but only traces the pixels in `rect`, leaving the rest of `out` untouched
+pub fn raytrace_colors_rect(x: Pose, camera: &dyn Camera, scene: &Scene, background_color: Color, rect: Rect, out: &mut Colors) {
     let mut rng = ThreadRng::default();
 
     let iso = pose_to_mat4(x);
-    let i_proj = mat4_inv(proj);
-    let i = mat4_mul(iso, i_proj);
 
     let num_samples = 10;
     let cnorm = 1.0 / num_samples as f32;
     let max_depth = 10;
 
-    for y in 0..H {
-        for x in 0..W {
-            let total_c = (0..num_samples).map(|_| {
-                let mut c = vec3_zero();
-                let mut cs = vec3_zero();
+    // paths are traced one row of pixels at a time, each path tracking its column and transmittance
+    let mut rays = RayPacket::with_capacity(W);
+    let mut next_rays = RayPacket::with_capacity(W);
+    let mut paths: Vec<(usize,Color)> = Vec::with_capacity(W);
+    let mut hits = PacketHits::new(W);
+    let mut nearest = PacketHits::new(W);
+    let mut nearest_c = vec![vec3_zero(); W];
+    let mut total_c = vec![vec3_zero(); W];
+
+    for y in rect.y0..rect.y1 {
+        total_c.fill(vec3_zero());
+
+        for _ in 0..num_samples {
+            rays.clear();
+            paths.clear();
+            for x in rect.x0..rect.x1 {
                 // the one place we add sampling INTERNAL to the ray-tracer: dithering
                 let u = x as f32 + u01(&mut rng) as f32 + 0.5;
                 let v = (H - y) as f32 + u01(&mut rng) as f32 - 0.5;
-                let near_pw = unproject_inv([u, v, -1.0], i, VIEWPORT);
-                let far_pw  = unproject_inv([u, v,  1.0], i, VIEWPORT);
-
-                let mut ray_origin = near_pw;
-                let mut ray_dir = vec3_sub(far_pw, near_pw);
-
-                let mut depth = max_depth;
-                let mut transmittance = [1.0; 3];
-                while depth > 0 {
+                if let Some((ray_origin, mut ray_dir)) = camera_ray(camera, iso, [u, v]) {
                     vec3_normalize(&mut ray_dir);
+                    rays.push(ray_origin, ray_dir);
+                    paths.push((x, [1.0; 3]));
+                }
+            }
 
-                    let mut distance = f32::MAX;
-                    let mut hit = false;
-                    let mut normalv = vec3_zero();
-
-                    for s in scene.iter() {
-                        if let Some((d, n)) = s.0.ray_intersect_reflect( ray_origin, ray_dir) {
-                            if d < distance {
-                                hit = true;
-                                distance = d;
-                                normalv = n;
-                                cs = s.1;
+            let mut depth = max_depth;
+            while depth > 0 && !rays.is_empty() {
+                let n = rays.len();
+                nearest.distances[..n].fill(f32::MAX);
+
+                for s in scene.iter() {
+                    s.0.ray_intersect_reflect_packet(&rays, &mut hits);
+                    for (i, c) in nearest_c.iter_mut().enumerate().take(n) {
+                        if hits.distances[i] < nearest.distances[i] {
+                            nearest.distances[i] = hits.distances[i];
+                            for j in 0..=2 {
+                                nearest.normals[j][i] = hits.normals[j][i];
                             }
+                            *c = s.1;
                         }
                     }
+                }
 
-                    if hit {
-                        ray_origin = ray_at(ray_origin, ray_dir, distance);
-                        ray_dir = vec3_add(normalv, uniform_s2.random(&mut rng, ()));
+                // continue the paths that hit something, and terminate the rest at the background
+                next_rays.clear();
+                let mut k = 0;
+                for i in 0..n {
+                    let (px, mut transmittance) = paths[i];
+                    if nearest.distances[i] < f32::MAX {
+                        let ray_origin = ray_at(rays.origin(i), rays.dir(i), nearest.distances[i]);
+                        let mut ray_dir = vec3_add(nearest.normal(i), uniform_s2.random(&mut rng, ()));
+                        vec3_normalize(&mut ray_dir);
+                        next_rays.push(ray_origin, ray_dir);
 
-                        transmittance[0] *= cs[0];
-                        transmittance[1] *= cs[1];
-                        transmittance[2] *= cs[2];
+                        transmittance[0] *= nearest_c[i][0];
+                        transmittance[1] *= nearest_c[i][1];
+                        transmittance[2] *= nearest_c[i][2];
 
-                        depth -= 1;
+                        paths[k] = (px, transmittance);
+                        k += 1;
                     } else {
-                        c[0] += transmittance[0] * background_color[0];
-                        c[1] += transmittance[1] * background_color[1];
-                        c[2] += transmittance[2] * background_color[2];
-                        break;
+                        total_c[px][0] += transmittance[0] * background_color[0];
+                        total_c[px][1] += transmittance[1] * background_color[1];
+                        total_c[px][2] += transmittance[2] * background_color[2];
                     }
                 }
-                c
-            }).fold(vec3_zero(), |a, c| [a[0] + c[0], a[1] + c[1], a[2] + c[2]]);
+                paths.truncate(k);
+                std::mem::swap(&mut rays, &mut next_rays);
+
+                depth -= 1;
+            }
+        }
+
+        // normalize and apply gamma correction
+        let finv_gamma = 0.5;
+        for x in rect.x0..rect.x1 {
+            out[y * W + x][0] = (total_c[x][0]*cnorm).powf(finv_gamma);
+            out[y * W + x][1] = (total_c[x][1]*cnorm).powf(finv_gamma);
+            out[y * W + x][2] = (total_c[x][2]*cnorm).powf(finv_gamma);
+        }
+    }
+}
+
+/// returns a depth raytrace from each camera of a rig at pose `x`
+pub fn raytrace_rig_depths<C: Camera>(x: Pose, rig: &[(Pose, C)], scene: &Scene, out: &mut [Depths]) {
+    assert_eq!(rig.len(), out.len());
+    for ((p, camera), out) in rig.iter().zip(out.iter_mut()) {
+        raytrace_depths(pose_mul(x, *p), camera, scene, out);
+    }
+}
+
+/// returns a color raytrace from each camera of a rig at pose `x`
+pub fn raytrace_rig_colors<C: Camera>(x: Pose, rig: &[(Pose, C)], scene: &Scene, background_color: Color, out: &mut [Colors]) {
+    assert_eq!(rig.len(), out.len());
+    for ((p, camera), out) in rig.iter().zip(out.iter_mut()) {
+        raytrace_colors(pose_mul(x, *p), camera, scene, background_color, out);
+    }
+}
+
+
+/* incremental rendering */
+
+/// returns the pixels that may image a world-space sphere seen from pose `x`, or `None` if
+/// it can't be bounded on screen, as when it reaches behind the camera, is unbounded or the camera bends straight
+/// lines
+pub fn sphere_rect(x: Pose, camera: &dyn Camera, center: Vec3, radius: f32) -> Option<Rect> {
+    if !camera.preserves_lines() || !radius.is_finite() {
+        return None;
+    }
+    let iso_inv = mat4_inv(pose_to_mat4(x));
+    let c = mat4_mulv(iso_inv, [center[0], center[1], center[2], 1.0]);
+
+    // bound the projection of the sphere by that of its enclosing cube
+    let (mut u0, mut v0, mut u1, mut v1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for i in 0..8 {
+        let corner = [
+            c[0] + if i & 1 == 0 { -radius } else { radius },
+            c[1] + if i & 2 == 0 { -radius } else { radius },
+            c[2] + if i & 4 == 0 { -radius } else { radius }
+        ];
+        let uv = camera.project(corner)?;
+        u0 = u0.min(uv[0]); u1 = u1.max(uv[0]);
+        v0 = v0.min(uv[1]); v1 = v1.max(uv[1]);
+    }
+
+    // pixel (x, y) is sampled over window coordinates [x + 0.5, x + 1.5] x [H - y - 0.5, H - y + 0.5]
+    let clamp = |a: f32, n: usize| a.max(0.0).min(n as f32) as usize;
+    Some(Rect {
+        x0: clamp((u0 - 1.5).floor(), W),
+        y0: clamp((H as f32 - v1 - 0.5).floor(), H),
+        x1: clamp((u1 - 0.5).ceil() + 1.0, W),
+        y1: clamp((H as f32 - v0 + 0.5).ceil() + 1.0, H)
+    })
+}
 
-            // normalize and apply gamma correction
-            let finv_gamma = 0.5;
-            out[y * W + x][0] = (total_c[0]*cnorm).powf(finv_gamma);
-            out[y * W + x][1] = (total_c[1]*cnorm).powf(finv_gamma);
-            out[y * W + x][2] = (total_c[2]*cnorm).powf(finv_gamma);
+/// returns the distance from the center of a solid within a sphere of radius `radius` beyond which it changes the
+/// light reaching a surface by less than the fraction `tolerance` of the sky's. Under the uniform sky of
+/// `raytrace_colors`, the solid can only block or stand in for the light of the cap of directions it subtends, which
+/// is a fraction 2(1 - cos a) of the sky's, with sin a = radius / distance.
+pub fn light_radius(radius: f32, tolerance: f32) -> f32 {
+    let tolerance = tolerance.clamp(0.0, 2.0);
+    let sin2 = tolerance - 0.25 * tolerance * tolerance;
+    if sin2 > 0.0 { radius / sin2.sqrt() } else { f32::INFINITY }
+}
+
+/// returns the pixels whose color may differ between renders of two scenes seen from pose `x`, by more than the
+/// fraction `tolerance` of the sky's light for changes in the light reflected off the rest of the scene (see
+/// `light_radius`)
+pub fn changed_rect(x: Pose, camera: &dyn Camera, prev: &Scene, next: &Scene, tolerance: f32) -> Rect {
+    if prev.len() != next.len() {
+        return Rect::full();
+    }
+    let mut rect = Rect::default();
+    for (a, b) in prev.iter().zip(next.iter()) {
+        if a.0.same_as(b.0.as_ref()) && a.1 == b.1 {
+            continue;
+        }
+        for s in [&a.0, &b.0] {
+            let r = s.bounds().and_then(|(c, r)| sphere_rect(x, camera, c, light_radius(r, tolerance)));
+            match r {
+                Some(r) => rect = rect.union(&r),
+                None => return Rect::full()
+            }
+        }
+    }
+    rect
+}
+
+/// a color render of the last scene seen, which re-renders only the region affected by
+/// changed solids when the camera pose and background are unchanged
+pub struct ColorRenderCache {
+    /// the largest change in the light reaching a surface, as a fraction of the sky's, left out of re-renders
+    pub tolerance: f32,
+    camera: Box<dyn Camera>,
+    last: Option<(Pose, Scene, Color)>,
+    pixels: Colors
+}
+
+impl ColorRenderCache {
+    pub fn new(camera: Box<dyn Camera>) -> Self {
+        ColorRenderCache { tolerance: 0.05, camera, last: None, pixels: vec![[0.0; 3]; AREA] }
+    }
+
+    /// renders `scene` from pose `x`, returning the pixels and the region that was re-rendered
+    pub fn render(&mut self, x: Pose, scene: Scene, background_color: Color) -> (&Colors, Rect) {
+        let rect = match &self.last {
+            Some((prev_x, prev_scene, prev_background_color))
+                if *prev_x == x && *prev_background_color == background_color =>
+                    changed_rect(x, self.camera.as_ref(), prev_scene, &scene, self.tolerance),
+            _ => Rect::full()
+        };
+        if !rect.is_empty() {
+            raytrace_colors_rect(x, self.camera.as_ref(), &scene, background_color, rect, &mut self.pixels);
         }
+        self.last = Some((x, scene, background_color));
+        (&self.pixels, rect)
+    }
+
+    /// returns the pixels of the last render whose primary ray first hits a solid flagged in `foreground`
+    pub fn coverage(&self, foreground: &[bool]) -> Mask {
+        let (x, scene, _) = self.last.as_ref().expect("nothing rendered yet");
+        raytrace_coverage(*x, self.camera.as_ref(), scene, foreground)
     }
-}
\ No newline at end of file
+}
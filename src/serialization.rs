@@ -2,7 +2,8 @@ use std::{
     process::Command,
     mem::size_of,
     fs::{File, create_dir_all},
-    io::prelude::*
+    io::prelude::*,
+    sync::Arc
 };
 use crate::types::*;
 use crate::config::{H,W,AREA};
@@ -89,6 +90,12 @@ pub fn load_colors(path: &str) -> Colors {
     cs
 }
 
+/// loads a mask from a bitmap, scoring the pixels brighter than mid-gray
+pub fn load_mask(path: &str) -> Mask {
+    let cs = load_colors(path);
+    Mask::Pixels(Arc::new(cs.iter().map(|c| c[0] + c[1] + c[2] > 1.5).collect()))
+}
+
 fn stitch_video_from_disk(
     inpath: &str,
     outpath: &str, 
@@ -163,6 +170,22 @@ fn colors_to_raw(c: &Colors) -> Vec<u8> {
     out
 }
 
+fn side_by_side_raw(images: &[&Colors]) -> Vec<u8> {
+    let raws = images.iter().map(|c| colors_to_raw(c)).collect::<Vec<Vec<u8>>>();
+    let mut raw_combined = vec![];
+    for y in 0..H {
+        for raw in raws.iter() {
+            raw_combined.extend_from_slice(&raw[y*W*3..(y + 1)*W*3]);
+        }
+    }
+    raw_combined
+}
+
+fn heat_color(t: f32) -> Color {
+    let t = 3.0 * t.clamp(0.0, 1.0);
+    [(t - 2.0).clamp(0.0, 1.0), (t - 1.0).clamp(0.0, 1.0), t.min(1.0)]
+}
+
 
 // interface
 
@@ -200,6 +223,44 @@ pub fn save_depths2(path: &str, d1: &Depths, d2: &Depths) {
     save_colors2(path, &c1, &c2);
 }
 
+/// false colors of a per-pixel log-likelihood map: black at its most likely pixel, through red
+/// and yellow to white at its least likely one (and at impossible pixels)
+pub fn log_likelihood_colors(map: &[f64]) -> Colors {
+    let finite = map.iter().filter(|w| w.is_finite());
+    let best = finite.clone().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    let worst = finite.fold(f64::INFINITY, |a, &b| a.min(b));
+    let range = (best - worst).max(f64::MIN_POSITIVE);
+    map.iter()
+        .map(|w| heat_color(if w.is_finite() { ((best - w) / range) as f32 } else { 1.0 }))
+        .collect()
+}
+
+/// false colors of a per-pixel log-likelihood diff: white where it is zero, red where it is
+/// positive and blue where it is negative, saturating at the largest finite change
+pub fn log_likelihood_diff_colors(diff: &[f64]) -> Colors {
+    let scale = diff.iter().filter(|d| d.is_finite()).fold(f64::MIN_POSITIVE, |a, b| a.max(b.abs()));
+    diff.iter()
+        .map(|d| {
+            let t = (d / scale).clamp(-1.0, 1.0) as f32;
+            if t >= 0.0 { [1.0 - t, 1.0 - t, 1.0] } else { [1.0, 1.0 + t, 1.0 + t] }
+        })
+        .collect()
+}
+
+pub fn save_log_likelihood(path: &str, map: &[f64]) {
+    save_colors(path, &log_likelihood_colors(map));
+}
+
+pub fn save_log_likelihood_diff(path: &str, diff: &[f64]) {
+    save_colors(path, &log_likelihood_diff_colors(diff));
+}
+
+/// like `save_colors2`, with the log-likelihood heatmap of the observation given the render as a third panel
+pub fn save_colors2_log_likelihood(path: &str, observation: &Colors, render: &Colors, map: &[f64]) {
+    let heatmap = log_likelihood_colors(map);
+    save_bitmap_image(path, &side_by_side_raw(&[observation, render, &heatmap]), 3*W, H);
+}
+
 pub fn save_colors_video(path: &str, cs: &Vec<Colors>, framerate: u32) {
     let raws = cs.iter().map(|c| colors_to_raw(c)).collect::<Vec<Vec<u8>>>();
     save_bitmap_video(path, &raws, W, H, framerate);
@@ -1,9 +1,13 @@
+use std::any::Any;
+use std::sync::Arc;
 use crate::linear::*;
+use crate::config::{H,W};
 
 
 /* types */
 
 pub type Mat4 = [[f32; 4]; 4];
+pub type Vec2 = [f32; 2];
 pub type Vec3 = [f32; 3];
 pub type Vec4 = [f32; 4];
 pub type Quat = Vec4;
@@ -14,15 +18,185 @@ pub type Color = [f32; 3];  // in [0.0, 1.0] (BGR)
 
 pub type Depths = Vec<Depth>;
 pub type Colors = Vec<Color>;
+pub type Rgbd = (Colors, Depths);
+pub type Edges = (Depths, Vec<f32>); // gradient magnitudes in [0.0, 1.0], orientations in [-pi, pi]
 
 
-pub trait Solid {
+/// a half-open rectangle of pixels `[x0, x1) x [y0, y1)`, with rows counted from the top
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize
+}
+
+impl Rect {
+    pub const fn full() -> Self {
+        Rect { x0: 0, y0: 0, x1: W, y1: H }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    pub fn area(&self) -> usize {
+        if self.is_empty() { 0 } else { (self.x1 - self.x0) * (self.y1 - self.y0) }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        self.x0 <= x && x < self.x1 && self.y0 <= y && y < self.y1
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() { return *other; }
+        if other.is_empty() { return *self; }
+        Rect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1)
+        }
+    }
+}
+
+/// the pixels scored by an observation likelihood: all of them, a region of interest, or a per-pixel mask
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Mask {
+    #[default]
+    All,
+    Roi(Rect),
+    Pixels(Arc<Vec<bool>>)
+}
+
+impl Mask {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        match self {
+            Mask::All => true,
+            Mask::Roi(rect) => rect.contains(x, y),
+            Mask::Pixels(pixels) => pixels[y*W + x]
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            Mask::All => H*W,
+            Mask::Roi(rect) => rect.area(),
+            Mask::Pixels(pixels) => pixels.iter().filter(|&&p| p).count()
+        }
+    }
+}
+
+/// a packet of rays in structure-of-arrays layout
+#[derive(Clone, Debug, Default)]
+pub struct RayPacket {
+    pub origins: [Vec<f32>; 3],
+    pub dirs: [Vec<f32>; 3]
+}
+
+impl RayPacket {
+    pub fn with_capacity(n: usize) -> Self {
+        RayPacket {
+            origins: [Vec::with_capacity(n), Vec::with_capacity(n), Vec::with_capacity(n)],
+            dirs: [Vec::with_capacity(n), Vec::with_capacity(n), Vec::with_capacity(n)]
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.origins[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&mut self, ray_origin: Vec3, ray_dir: Vec3) {
+        for i in 0..=2 {
+            self.origins[i].push(ray_origin[i]);
+            self.dirs[i].push(ray_dir[i]);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for i in 0..=2 {
+            self.origins[i].clear();
+            self.dirs[i].clear();
+        }
+    }
+
+    pub fn origin(&self, i: usize) -> Vec3 {
+        [self.origins[0][i], self.origins[1][i], self.origins[2][i]]
+    }
+
+    pub fn dir(&self, i: usize) -> Vec3 {
+        [self.dirs[0][i], self.dirs[1][i], self.dirs[2][i]]
+    }
+}
+
+/// hit distances (`f32::INFINITY` on a miss) and reflection normals for a `RayPacket`
+#[derive(Clone, Debug, Default)]
+pub struct PacketHits {
+    pub distances: Vec<f32>,
+    pub normals: [Vec<f32>; 3]
+}
+
+impl PacketHits {
+    pub fn new(n: usize) -> Self {
+        PacketHits { distances: vec![f32::INFINITY; n], normals: [vec![0.0; n], vec![0.0; n], vec![0.0; n]] }
+    }
+
+    pub fn normal(&self, i: usize) -> Vec3 {
+        [self.normals[0][i], self.normals[1][i], self.normals[2][i]]
+    }
+}
+
+/// upcasts to `Any`, so that solids can recognize their own type in `Solid::same_as`
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait Solid: AsAny {
     fn ray_intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<f32>;
     fn ray_intersect_reflect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)>;
+
+    /// returns a bounding sphere (center, radius), or `None` if the solid is unbounded or its bounds are unknown
+    fn bounds(&self) -> Option<(Vec3,f32)> {
+        None
+    }
+
+    /// returns true if `other` is known to be the same solid; used to detect scene changes
+    fn same_as(&self, _other: &dyn Solid) -> bool {
+        false
+    }
+
+    /// intersects every ray of a packet, writing hit distances (`f32::INFINITY` on a miss) to `out`
+    fn ray_intersect_packet(&self, rays: &RayPacket, out: &mut [f32]) {
+        for (i, d) in out.iter_mut().enumerate().take(rays.len()) {
+            *d = self.ray_intersect(rays.origin(i), rays.dir(i)).unwrap_or(f32::INFINITY);
+        }
+    }
+
+    /// intersects every ray of a packet, writing hit distances and normals to `out`
+    fn ray_intersect_reflect_packet(&self, rays: &RayPacket, out: &mut PacketHits) {
+        for i in 0..rays.len() {
+            let (d, n) = self.ray_intersect_reflect(rays.origin(i), rays.dir(i)).unwrap_or((f32::INFINITY, vec3_zero()));
+            out.distances[i] = d;
+            for (normals, n) in out.normals.iter_mut().zip(n) {
+                normals[i] = n;
+            }
+        }
+    }
 }
 
 pub type Scene = Vec<(Box<dyn Solid>,Color)>;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Plane {
     pub origin: Vec3,
     pub normal: Vec3
@@ -44,8 +218,38 @@ impl Solid for Plane {
         let d = vec3_dot(&vec3_sub(origin, ray_origin), &normalv) / denom;
         if d > 1e-6 { Some((d, normalv)) } else { None }
     }
+
+    fn bounds(&self) -> Option<(Vec3,f32)> {
+        None
+    }
+
+    fn same_as(&self, other: &dyn Solid) -> bool {
+        other.as_any().downcast_ref::<Plane>() == Some(self)
+    }
+
+    fn ray_intersect_packet(&self, rays: &RayPacket, out: &mut [f32]) {
+        let [nx, ny, nz] = self.normal;
+        let n = rays.len();
+        let [ox, oy, oz] = [&rays.origins[0][..n], &rays.origins[1][..n], &rays.origins[2][..n]];
+        let [dx, dy, dz] = [&rays.dirs[0][..n], &rays.dirs[1][..n], &rays.dirs[2][..n]];
+        let out = &mut out[..n];
+        for i in 0..n {
+            let denom = nx * dx[i] + ny * dy[i] + nz * dz[i];
+            let num = (self.origin[0] - ox[i]) * nx + (self.origin[1] - oy[i]) * ny + (self.origin[2] - oz[i]) * nz;
+            let d = num / denom;
+            out[i] = if d > 1e-6 { d } else { f32::INFINITY };
+        }
+    }
+
+    fn ray_intersect_reflect_packet(&self, rays: &RayPacket, out: &mut PacketHits) {
+        self.ray_intersect_packet(rays, &mut out.distances);
+        for j in 0..=2 {
+            out.normals[j][..rays.len()].fill(self.normal[j]);
+        }
+    }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32
@@ -81,6 +285,33 @@ impl Sphere {
 
         Some(t1)
     }
+
+    /// branch-free counterpart of `intersect` over a packet of rays
+    fn intersect_packet(&self, rays: &RayPacket, out: &mut [f32]) {
+        let [cx, cy, cz] = self.center;
+        let r2 = self.radius * self.radius;
+        let n = rays.len();
+        let [ox, oy, oz] = [&rays.origins[0][..n], &rays.origins[1][..n], &rays.origins[2][..n]];
+        let [dx, dy, dz] = [&rays.dirs[0][..n], &rays.dirs[1][..n], &rays.dirs[2][..n]];
+        let out = &mut out[..n];
+        for i in 0..n {
+            let (px, py, pz) = (cx - ox[i], cy - oy[i], cz - oz[i]);
+            let ddp = dx[i] * px + dy[i] * py + dz[i] * pz;
+            let dpp = px * px + py * py + pz * pz;
+
+            // remedy term for numerical stability
+            let (rx, ry, rz) = (px - dx[i] * ddp, py - dy[i] * ddp, pz - dz[i] * ddp);
+
+            let dscr = r2 - (rx * rx + ry * ry + rz * rz);
+            let sq = dscr.max(0.0).sqrt();
+            let q = if ddp >= 0.0 { ddp + sq } else { ddp - sq };
+            let t = (dpp - r2) / q;
+            let (t1, t2) = (q.min(t), q.max(t));
+
+            let miss = dscr < 0.0 || (t1 < 0.0 && t2 < 0.0);
+            out[i] = if miss { f32::INFINITY } else { t1 };
+        }
+    }
 }
 
 impl Solid for Sphere {
@@ -95,4 +326,158 @@ impl Solid for Sphere {
             None
         }
     }
+
+    fn bounds(&self) -> Option<(Vec3,f32)> {
+        Some((self.center, self.radius))
+    }
+
+    fn same_as(&self, other: &dyn Solid) -> bool {
+        other.as_any().downcast_ref::<Sphere>() == Some(self)
+    }
+
+    fn ray_intersect_packet(&self, rays: &RayPacket, out: &mut [f32]) {
+        self.intersect_packet(rays, out);
+    }
+
+    fn ray_intersect_reflect_packet(&self, rays: &RayPacket, out: &mut PacketHits) {
+        self.intersect_packet(rays, &mut out.distances);
+        let n = rays.len();
+        let [ox, oy, oz] = [&rays.origins[0][..n], &rays.origins[1][..n], &rays.origins[2][..n]];
+        let [dx, dy, dz] = [&rays.dirs[0][..n], &rays.dirs[1][..n], &rays.dirs[2][..n]];
+        let [nx, ny, nz] = &mut out.normals;
+        let [nx, ny, nz] = [&mut nx[..n], &mut ny[..n], &mut nz[..n]];
+        let d = &out.distances[..n];
+        for i in 0..n {
+            nx[i] = ox[i] + dx[i] * d[i] - self.center[0];
+            ny[i] = oy[i] + dy[i] * d[i] - self.center[1];
+            nz[i] = oz[i] + dz[i] * d[i] - self.center[2];
+        }
+    }
+}
+
+/// a box with half side lengths `half_extents` along the axes of its local frame, rotated by `rotation` about its center
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cuboid {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub rotation: Quat
+}
+
+impl Cuboid {
+    /// nearest hit distance past the ray origin and local normal, by slabs in the local frame
+    fn intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)> {
+        let inv = quat_conj(self.rotation);
+        let o = quat_rotatev(inv, vec3_sub(ray_origin, self.center));
+        let d = quat_rotatev(inv, ray_dir);
+        let (mut t0, mut t1) = (f32::NEG_INFINITY, f32::INFINITY);
+        let (mut axis0, mut axis1) = (0, 0);
+        for i in 0..=2 {
+            // a ray parallel to a slab misses it or stays within it
+            if d[i] == 0.0 {
+                if o[i].abs() > self.half_extents[i] { return None; }
+                continue;
+            }
+            let (a, b) = ((-self.half_extents[i] - o[i]) / d[i], (self.half_extents[i] - o[i]) / d[i]);
+            let (near, far) = (a.min(b), a.max(b));
+            if near > t0 { t0 = near; axis0 = i; }
+            if far < t1 { t1 = far; axis1 = i; }
+        }
+        if t0 > t1 { return None; }
+        let (t, axis, sign) = if t0 > 1e-4 {
+            (t0, axis0, -d[axis0].signum())
+        } else if t1 > 1e-4 {
+            (t1, axis1, d[axis1].signum())
+        } else {
+            return None;
+        };
+        let mut n = vec3_zero();
+        n[axis] = sign;
+        Some((t, n))
+    }
+}
+
+impl Solid for Cuboid {
+    fn ray_intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<f32> {
+        self.intersect(ray_origin, ray_dir).map(|(d, _)| d)
+    }
+
+    fn ray_intersect_reflect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)> {
+        self.intersect(ray_origin, ray_dir).map(|(d, n)| (d, quat_rotatev(self.rotation, n)))
+    }
+
+    fn bounds(&self) -> Option<(Vec3,f32)> {
+        Some((self.center, vec3_norm(&self.half_extents)))
+    }
+
+    fn same_as(&self, other: &dyn Solid) -> bool {
+        other.as_any().downcast_ref::<Cuboid>() == Some(self)
+    }
+}
+
+/// a capped cylinder of radius `radius` whose axis is the local y axis, with half its height `half_height`, rotated by
+/// `rotation` about its center
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cylinder {
+    pub center: Vec3,
+    pub radius: f32,
+    pub half_height: f32,
+    pub rotation: Quat
+}
+
+impl Cylinder {
+    /// nearest hit distance past the ray origin and local normal, among the side and the two caps
+    fn intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)> {
+        let inv = quat_conj(self.rotation);
+        let o = quat_rotatev(inv, vec3_sub(ray_origin, self.center));
+        let d = quat_rotatev(inv, ray_dir);
+        let (mut t_min, mut nearest) = (f32::INFINITY, None);
+        let mut consider = |t: f32, n: Vec3| {
+            if t > 1e-4 && t < t_min {
+                t_min = t;
+                nearest = Some((t, n));
+            }
+        };
+
+        // side
+        let a = d[0] * d[0] + d[2] * d[2];
+        let b = o[0] * d[0] + o[2] * d[2];
+        let c = o[0] * o[0] + o[2] * o[2] - self.radius * self.radius;
+        let dscr = b * b - a * c;
+        if a > 0.0 && dscr >= 0.0 {
+            for t in [(-b - dscr.sqrt()) / a, (-b + dscr.sqrt()) / a] {
+                let p = ray_at(o, d, t);
+                if p[1].abs() <= self.half_height {
+                    consider(t, [p[0] / self.radius, 0.0, p[2] / self.radius]);
+                }
+            }
+        }
+
+        // caps
+        for sign in [-1.0, 1.0] {
+            let t = (sign * self.half_height - o[1]) / d[1];
+            let p = ray_at(o, d, t);
+            if p[0] * p[0] + p[2] * p[2] <= self.radius * self.radius {
+                consider(t, [0.0, sign, 0.0]);
+            }
+        }
+        nearest
+    }
+}
+
+impl Solid for Cylinder {
+    fn ray_intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<f32> {
+        self.intersect(ray_origin, ray_dir).map(|(d, _)| d)
+    }
+
+    fn ray_intersect_reflect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)> {
+        self.intersect(ray_origin, ray_dir).map(|(d, n)| (d, quat_rotatev(self.rotation, n)))
+    }
+
+    fn bounds(&self) -> Option<(Vec3,f32)> {
+        Some((self.center, self.radius.hypot(self.half_height)))
+    }
+
+    fn same_as(&self, other: &dyn Solid) -> bool {
+        other.as_any().downcast_ref::<Cylinder>() == Some(self)
+    }
 }
@@ -0,0 +1,85 @@
+use modppl::prelude::*;
+use modppl_derender::*;
+
+
+fn inv_temps(schedule: Schedule, accept: bool) -> Vec<f64> {
+    let mut annealing = Annealing::new(schedule);
+    let mut inv_temps = vec![];
+    while let Some(inv_temp) = annealing.inv_temp() {
+        inv_temps.push(inv_temp);
+        annealing.advance(accept);
+    }
+    inv_temps
+}
+
+#[test]
+fn test_tempered_likelihood() {
+    let pixels = vec![0.4; AREA];
+    let noisy = vec![0.45; AREA];
+    let w = noisy_depths.logpdf(&noisy, (pixels.clone(), 0.1, 0.1));
+    let tempered = Tempered { dist: noisy_depths, inv_temp: 0.25 };
+    assert!((tempered.logpdf(&noisy, (pixels.clone(), 0.1, 0.1)) - 0.25 * w).abs() < 1e-9 * w.abs());
+    let flat = Tempered { dist: noisy_depths, inv_temp: 0.0 };
+    assert_eq!(flat.logpdf(&vec![2.0; AREA], (pixels, 0.1, 0.1)), 0.0);
+}
+
+#[test]
+fn test_schedules() {
+    let linear = inv_temps(Schedule::Linear { from: 0.1, to: 1.0, steps: 4 }, true);
+    assert_eq!(linear.len(), 4);
+    for (a, b) in linear.iter().zip([0.1, 0.4, 0.7, 1.0]) {
+        assert!((a - b).abs() < 1e-12, "{:?}", linear);
+    }
+    let ratios = inv_temps(Schedule::Geometric { from: 0.01, to: 1.0, steps: 3 }, true);
+    for (a, b) in ratios.iter().zip([0.01, 0.1, 1.0]) {
+        assert!((a - b).abs() < 1e-12, "{:?}", ratios);
+    }
+    assert!(inv_temps(Schedule::Linear { from: 0.1, to: 1.0, steps: 0 }, true).is_empty());
+
+    // the adaptive schedule cools as fast as its rate allows while proposals are accepted, and slower otherwise
+    let schedule = Schedule::Adaptive { from: 0.01, to: 1.0, rate: 0.5, target_accept: 0.3 };
+    let fast = inv_temps(schedule.clone(), true);
+    let slow = inv_temps(schedule, false);
+    assert_eq!(*fast.last().unwrap(), 1.0);
+    assert_eq!(*slow.last().unwrap(), 1.0);
+    assert_eq!(fast.len(), (100f64.ln() / 1.5f64.ln()).ceil() as usize + 1);
+    assert!(slow.len() > 2 * fast.len());
+    assert!(fast.windows(2).chain(slow.windows(2)).all(|t| t[0] < t[1]));
+
+    // schedules that would never end or scale zero are rejected
+    for schedule in [
+        Schedule::Adaptive { from: 0.0, to: 1.0, rate: 0.5, target_accept: 0.3 },
+        Schedule::Adaptive { from: 0.1, to: 1.0, rate: 0.0, target_accept: 0.3 },
+        Schedule::Geometric { from: 0.0, to: 1.0, steps: 3 },
+        Schedule::Linear { from: 1.0, to: 0.1, steps: 3 }
+    ] {
+        assert!(std::panic::catch_unwind(|| Annealing::new(schedule.clone())).is_err(), "{:?}", schedule);
+    }
+}
+
+#[test]
+fn test_anneal_ball() {
+    let mut constraints = DynTrie::new();
+    constraints.observe("observation", Arc::new(load_colors("./tests/ball.bmp")));
+    let trace = ball_model.generate(ObsParams::default(), constraints).0;
+
+    let mut ball_mask = AddrMap::new();
+    ball_mask.visit("ball_u");
+    ball_mask.visit("ball_v");
+    let mut seen = vec![];
+    let schedule = Schedule::Geometric { from: 0.001, to: 1.0, steps: 4 };
+    let trace = anneal(&ball_model, trace, schedule, |trace| {
+        seen.push(trace.args.inv_temp);
+        regen_mh(&ball_model, trace, &ball_mask)
+    });
+    assert_eq!(seen.len(), 4);
+    assert!((seen[1] - 0.01).abs() < 1e-12);
+
+    // the result is scored by the untempered posterior
+    assert_eq!(trace.args.inv_temp, 1.0);
+    let observation = trace.data.read::<Colors>("observation");
+    let w = |t: &DynTrace<ObsParams,Colors>| noisy_colors.logpdf(&observation, (t.retv.clone().unwrap(), OUTLIER_PROB, PIXEL_NOISE));
+    let rescored = ball_model.generate(ObsParams::default(), trace.data.clone()).0;
+    let prior = rescored.logjp - w(&rescored);
+    assert!((trace.logjp - prior - w(&trace)).abs() < 1e-6 * trace.logjp.abs());
+}
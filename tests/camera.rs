@@ -0,0 +1,137 @@
+use std::f32::consts::PI;
+use modppl_derender::*;
+
+
+fn assert_round_trips(camera: &dyn Camera) {
+    // window -> ray -> window
+    for &uv in [[64.0, 64.0], [3.5, 120.25], [100.0, 17.0], [127.0, 1.0]].iter() {
+        let (o, d) = camera.unproject(uv).expect("pixel should have a ray");
+        for &t in [0.5, 1.0, 4.0].iter() {
+            let p = ray_at(o, d, t);
+            let uv2 = camera.project(p).expect("point on ray should be in view");
+            assert!((uv[0] - uv2[0]).abs() < 1e-2, "{:?} != {:?}", uv, uv2);
+            assert!((uv[1] - uv2[1]).abs() < 1e-2, "{:?} != {:?}", uv, uv2);
+        }
+    }
+
+    // point -> window -> ray through point
+    for &p in [[0.0, 0.0, -2.0], [0.3, -0.2, -1.0], [-1.0, 0.5, -3.0]].iter() {
+        let uv = camera.project(p).expect("point should be in view");
+        let (o, d) = camera.unproject(uv).expect("pixel should have a ray");
+        let op = vec3_sub(p, o);
+        let t = vec3_dot(&op, &d);
+        let err = vec3_norm(&vec3_sub(op, vec3_scale(&d, t)));
+        assert!(err < 1e-3, "ray misses {:?} by {}", p, err);
+    }
+}
+
+#[test]
+fn test_perspective_round_trip() {
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    assert_round_trips(&camera);
+    assert!(camera.project([0.0, 0.0, 1.0]).is_none());
+}
+
+#[test]
+fn test_orthographic_round_trip() {
+    let camera = ProjectiveCamera::orthographic(4.0, 4.0, NEAR, FAR);
+    assert_round_trips(&camera);
+
+    // all rays are parallel to the optical axis
+    let (_, d) = camera.unproject([10.0, 90.0]).unwrap();
+    assert!(vec3_norm(&vec3_sub(d, [0.0, 0.0, -1.0])) < 1e-5);
+}
+
+#[test]
+fn test_fisheye_round_trip() {
+    for model in [FisheyeModel::Equidistant, FisheyeModel::Equisolid] {
+        let camera = FisheyeCamera::new(model, PI, NEAR);
+        assert_round_trips(&camera);
+
+        // a 180 degree lens sees points at the side of the camera on the image border
+        let uv = camera.project([1.0, 0.0, 0.0]).unwrap();
+        assert!((uv[0] - W as f32).abs() < 1e-3 && (uv[1] - 0.5 * H as f32).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_orthographic_depths() {
+    // a top-down orthographic view of the ground has constant depth
+    let x = vec3_euler_to_pose([0.0, 2.0, 0.0], [-PI/2.0, 0.0, 0.0]);
+    let camera = ProjectiveCamera::orthographic(4.0, 4.0, NEAR, FAR);
+    let ground = (
+        Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>,
+        [0.0, 0.0, 0.0]
+    );
+    let mut pixels = vec![0.0; AREA];
+    raytrace_depths(x, &camera, &vec![ground], &mut pixels);
+
+    let expected = 1.0 - (2.0 - NEAR - NEAR) / (FAR - NEAR);
+    for p in pixels.iter() {
+        assert!((p - expected).abs() < 1e-4, "{} != {}", p, expected);
+    }
+}
+
+#[test]
+fn test_distortion_round_trip() {
+    let distortion = Distortion { k1: -0.28, k2: 0.07, k3: -0.01, p1: 1e-3, p2: -5e-4 };
+    for &p in [[0.0, 0.0], [0.4, -0.3], [-0.8, 0.7], [0.95, 0.95]].iter() {
+        let q = distortion.undistort(distortion.distort(p));
+        assert!((p[0] - q[0]).abs() < 1e-4 && (p[1] - q[1]).abs() < 1e-4, "{:?} != {:?}", p, q);
+    }
+}
+
+#[test]
+fn test_distorted_camera() {
+    let f = 0.5 * W as f32;
+    let pinhole = ProjectiveCamera::from_intrinsics(f, f, 0.5 * W as f32, 0.5 * H as f32, Distortion::default(), NEAR, FAR);
+    let expected = perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    for (a, b) in pinhole.proj.iter().flatten().zip(expected.iter().flatten()) {
+        assert!((a - b).abs() < 1e-5);
+    }
+
+    let distortion = Distortion { k1: -0.2, k2: 0.05, k3: 0.0, p1: 2e-3, p2: 1e-3 };
+    let camera = ProjectiveCamera::from_intrinsics(f, 1.1 * f, 60.0, 70.0, distortion, NEAR, FAR);
+    assert_round_trips(&camera);
+
+    // barrel distortion pulls points near the border towards the principal point
+    let p = [-1.0, 0.9, -1.5];
+    let ideal = ProjectiveCamera::from_intrinsics(f, 1.1 * f, 60.0, 70.0, Distortion::default(), NEAR, FAR);
+    let uv = camera.project(p).unwrap();
+    let uv_ideal = ideal.project(p).unwrap();
+    let c = [60.0, H as f32 - 70.0];
+    let r = ((uv[0] - c[0]).powi(2) + (uv[1] - c[1]).powi(2)).sqrt();
+    let r_ideal = ((uv_ideal[0] - c[0]).powi(2) + (uv_ideal[1] - c[1]).powi(2)).sqrt();
+    assert!(r < r_ideal - 1.0, "{} >= {}", r, r_ideal);
+}
+
+#[test]
+fn test_pose_mul() {
+    let a = vec3_euler_to_pose([0.3, 1.5, 1.2], [0.2, -0.4, 0.1]);
+    let b = vec3_euler_to_pose([-0.1, 0.2, 0.05], [0.0, 0.3, -0.2]);
+    let expected = mat4_mul(pose_to_mat4(a), pose_to_mat4(b));
+    let m = pose_to_mat4(pose_mul(a, b));
+    for (a, b) in m.iter().flatten().zip(expected.iter().flatten()) {
+        assert!((a - b).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_stereo_rig_depths() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let rig = stereo_rig(0.2, PI/2.0, NEAR, FAR);
+    let scene = || vec![
+        (Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>, [0.5; 3]),
+        (Box::new(Sphere { center: [0.0, 0.5, -1.0], radius: 0.5 }) as Box<dyn Solid>, [0.5; 3])
+    ];
+
+    let mut views = vec![vec![0.0; AREA]; rig.len()];
+    raytrace_rig_depths(x, &rig, &scene(), &mut views);
+
+    for (i, (p, camera)) in rig.iter().enumerate() {
+        let mut pixels = vec![0.0; AREA];
+        raytrace_depths(pose_mul(x, *p), camera, &scene(), &mut pixels);
+        assert_eq!(pixels, views[i]);
+    }
+    assert_ne!(views[0], views[1]);
+}
@@ -0,0 +1,94 @@
+use modppl::prelude::*;
+use modppl_derender::*;
+
+
+fn assert_close(a: Vec3, b: Vec3, tol: f32) {
+    for i in 0..=2 {
+        assert!((a[i] - b[i]).abs() < tol, "{:?} != {:?}", a, b);
+    }
+}
+
+fn grid() -> Vec<Color> {
+    let steps = [0.0, 0.1, 0.35, 0.5, 0.8, 1.0];
+    let mut colors = vec![];
+    for b in steps {
+        for g in steps {
+            for r in steps {
+                colors.push([b, g, r]);
+            }
+        }
+    }
+    colors
+}
+
+#[test]
+fn test_color_conversions() {
+    assert_close(bgr_to_lab([1.0, 1.0, 1.0]), [100.0, 0.0, 0.0], 1e-2);
+    assert_close(bgr_to_lab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0], 1e-4);
+    assert_close(bgr_to_lab([0.0, 0.0, 1.0]), [53.24, 80.09, 67.20], 5e-2);
+    assert_close(bgr_to_hsv([0.0, 0.0, 1.0]), [0.0, 1.0, 1.0], 1e-6);
+    assert_close(bgr_to_hsv([1.0, 0.0, 0.0]), [2.0 / 3.0, 1.0, 1.0], 1e-6);
+    assert_close(bgr_to_hsv([0.5, 0.5, 0.5]), [0.0, 0.0, 0.5], 1e-6);
+    for c in grid() {
+        assert_close(lab_to_bgr(bgr_to_lab(c)), c, 1e-3);
+        assert_close(hsv_to_bgr(bgr_to_hsv(c)), c, 1e-5);
+        for space in [ColorSpace::Lab, ColorSpace::Hsv] {
+            assert_close(space.color(space.coords(c)), c, 1e-3);
+            let (coords, ranges) = (space.coords(c), space.ranges());
+            assert!((0..=2).all(|i| ranges[i].0 - 1e-5 <= coords[i] && coords[i] <= ranges[i].1 + 1e-5), "{:?}", c);
+        }
+    }
+}
+
+#[test]
+fn test_perceptual_saturated() {
+    // saturated blues lie beyond b/100 = -1, where their differences in b still count
+    let blue = vec![[1.0, 0.0, 0.0]; AREA];
+    let purple = vec![[1.0, 0.0, 0.1]; AREA];
+    let (coords, purple_coords) = (ColorSpace::Lab.coords(blue[0]), ColorSpace::Lab.coords(purple[0]));
+    assert!(coords[2] < purple_coords[2] - 0.005 && purple_coords[2] < -1.05);
+    assert!((coords[1] - purple_coords[1]).abs() < 0.002);
+    let dist = PerceptualColors { space: ColorSpace::Lab, mask: Mask::All };
+    let w = |pixels: &Colors| dist.logpdf(&blue, (pixels.clone(), 0.01, 1.0, 0.002));
+    assert!(w(&blue) > w(&purple) + AREA as f64, "{} {}", w(&blue), w(&purple));
+    let map = dist.log_likelihood_map(&blue, (purple.clone(), 0.01, 1.0, 0.002));
+    assert!((map.iter().sum::<f64>() - w(&purple)).abs() < 1e-6 * w(&purple).abs());
+}
+
+#[test]
+fn test_perceptual_shading() {
+    // the same orange under dimmer light, and a yellow of the same lightness
+    let base = vec![[0.2, 0.5, 0.9]; AREA];
+    let shaded = vec![[0.14, 0.35, 0.63]; AREA];
+    let hsv = bgr_to_hsv(base[0]);
+    let hue_shifted = vec![hsv_to_bgr([hsv[0] + 0.06, hsv[1], hsv[2]]); AREA];
+
+    for space in [ColorSpace::Lab, ColorSpace::Hsv] {
+        let dist = PerceptualColors { space, mask: Mask::All };
+        let w_shaded = dist.logpdf(&shaded, (base.clone(), 0.01, 0.5, 0.05));
+        let w_hue = dist.logpdf(&hue_shifted, (base.clone(), 0.01, 0.5, 0.05));
+        assert!(w_shaded > w_hue, "{:?}: {} {}", space, w_shaded, w_hue);
+
+        let samples = dist.random(&mut ThreadRng::default(), (base.clone(), 0.1, 0.1, 0.1));
+        assert_eq!(samples.len(), AREA);
+        assert!(samples.iter().all(|c| c.iter().all(|v| (0.0..=1.0).contains(v))));
+    }
+
+    // whereas channel-wise noise penalizes the shading more
+    let w_shaded = noisy_colors.logpdf(&shaded, (base.clone(), 0.01, 0.1));
+    let w_hue = noisy_colors.logpdf(&hue_shifted, (base, 0.01, 0.1));
+    assert!(w_shaded < w_hue);
+}
+
+#[test]
+fn test_select_color_space() {
+    let mut constraints = DynTrie::new();
+    constraints.observe("observation", Arc::new(load_colors("./tests/ball.bmp")));
+    for space in [ColorSpace::Lab, ColorSpace::Hsv] {
+        let obs = ObsParams { color_space: Some(space), chroma_sigma: 0.05, ..ObsParams::default() };
+        let (trace, weight) = ball_model.generate(obs, constraints.clone());
+        assert!(weight.is_finite());
+        let outliers = color_outlier_map(&trace).unwrap();
+        assert!(outliers.iter().all(|p| (0.0..=1.0).contains(p)));
+    }
+}
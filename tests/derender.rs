@@ -1,6 +1,7 @@
 use modppl::prelude::*;
 use modppl_derender::*;
 use std::fs::create_dir_all;
+use std::f32::consts::PI;
 
 
 #[test]
@@ -11,13 +12,13 @@ fn test_derender_ground_depth() {
     let mut synth_constraints = DynTrie::new();
     synth_constraints.observe("cam_roll", Arc::new(0.));
     synth_constraints.observe("cam_y", Arc::new(1.5));
-    let trace = grounded_depth_model.generate((), synth_constraints).0;
+    let trace = grounded_depth_model.generate(ObsParams::default(), synth_constraints).0;
 
     // generate trace
     let mut constraints = DynTrie::new();
     let observation = trace.data.read::<Depths>("observation").clone();
     constraints.observe("observation", Arc::new(observation.clone()));
-    let mut trace = grounded_depth_model.generate((), constraints).0;
+    let mut trace = grounded_depth_model.generate(ObsParams::default(), constraints).0;
 
     let mut cam_mask = AddrMap::new();
     cam_mask.visit("cam_y");
@@ -46,13 +47,13 @@ fn test_derender_sphere_color() {
         synth_constraints.observe("cam_roll", Arc::new(0.));
         synth_constraints.observe("ground_albedo", Arc::new(0.5));
         synth_constraints.observe("ambient_brightness", Arc::new(0.95));
-        let trace = sphere_color_model.generate((), synth_constraints).0;
+        let trace = sphere_color_model.generate(ObsParams::default(), synth_constraints).0;
 
         // generate trace
         let mut constraints = DynTrie::new();
         let observation = trace.data.read::<Colors>("observation").clone();
         constraints.observe("observation", Arc::new(observation.clone()));
-        let mut trace = sphere_color_model.generate((), constraints).0;
+        let mut trace = sphere_color_model.generate(ObsParams::default(), constraints).0;
 
         let mut cam_mask = AddrMap::new();
         cam_mask.visit("cam_y");
@@ -99,7 +100,7 @@ fn test_derender_ball() {
     // generate trace
     let mut constraints = DynTrie::new();
     constraints.observe("observation", Arc::new(observation.clone()));
-    let mut trace = ball_model.generate((), constraints).0;
+    let mut trace = ball_model.generate(ObsParams::default(), constraints).0;
 
     let mut cam_mask = AddrMap::new();
     cam_mask.visit("cam_y");
@@ -141,4 +142,585 @@ fn test_derender_ball() {
 
     let observations = vec![observation; NUM_ITERS];
     save_colors2_video("./out/ball.mp4", &observations, &renders, 20);
-}
\ No newline at end of file
+}
+
+fn ball_latents(table_c: [f64; 3], ball_u: f64, ball_c: [f64; 3], ball_radius: f64) -> DynTrie {
+    let mut constraints = DynTrie::new();
+    for (addr, value) in [("cam_y", 1.2), ("cam_yaw", 0.), ("ambient_brightness", 0.9), ("table_c0", table_c[0]),
+                          ("table_c1", table_c[1]), ("table_c2", table_c[2]), ("ball_u", ball_u), ("ball_v", -0.5),
+                          ("ball_c0", ball_c[0]), ("ball_c1", ball_c[1]), ("ball_c2", ball_c[2]),
+                          ("ball_radius", ball_radius)] {
+        constraints.observe(addr, Arc::new(value));
+    }
+    constraints
+}
+
+#[test]
+fn test_stereo_ball_views() {
+    // a red ball straight ahead of a level rig, on a blue table
+    let constraints = ball_latents([0.2, 0.3, 0.8], 0.0, [0.9, 0.25, 0.25], 0.3);
+    let rig = stereo_rig(0.12, PI/2.0, NEAR, FAR);
+    let trace = stereo_ball_model.generate((ObsParams::default(), rig.clone()), constraints).0;
+    let views = trace.retv.clone().unwrap();
+    assert_eq!(views.len(), 2);
+
+    // the ball's image shifts between the views by its disparity, the focal length times the baseline over its depth
+    let centroid_x = |pixels: &Colors| {
+        let (sum, count) = pixels.iter().enumerate()
+            .filter(|(_, c)| c[0] > c[2] + 0.2)
+            .fold((0.0, 0.0), |(sum, count), (i, _)| (sum + (i % W) as f32, count + 1.0));
+        sum / count
+    };
+    let disparity = centroid_x(&views[0]) - centroid_x(&views[1]);
+    let expected = 0.5 * H as f32 * 0.12 / 1.7;
+    assert!((disparity - expected).abs() < 1.0, "{} {}", disparity, expected);
+
+    // condition on both views at once
+    let mut constraints = DynTrie::new();
+    for i in 0..views.len() {
+        let observation = trace.data.read::<Colors>(&format!("observation_{i}")).clone();
+        constraints.observe(&format!("observation_{i}"), Arc::new(observation));
+    }
+    let (trace, weight) = stereo_ball_model.generate((ObsParams::default(), rig), constraints);
+    assert!(weight.is_finite());
+    assert_eq!(trace.retv.unwrap().len(), 2);
+
+    // or on those of any other layout, here a wider pair with a third camera above it looking down at the table
+    let above = vec3_euler_to_pose([0.0, 0.5, 0.0], [-0.4, 0.0, 0.0]);
+    let mut rig = stereo_rig(0.3, PI/2.0, NEAR, FAR);
+    rig.push((above, ProjectiveCamera::perspective(PI/3.0, W as f32/H as f32, NEAR, FAR)));
+    let constraints = ball_latents([0.2, 0.3, 0.8], 0.0, [0.9, 0.25, 0.25], 0.3);
+    let trace = stereo_ball_model.generate((ObsParams::default(), rig), constraints).0;
+    let views = trace.retv.clone().unwrap();
+    assert_eq!(views.len(), 3);
+    let expected = 0.5 * H as f32 * 0.3 / 1.7;
+    assert!((centroid_x(&views[0]) - centroid_x(&views[1]) - expected).abs() < 1.0);
+    assert!(views[2] != views[0] && trace.data.search("observation_2").is_some());
+}
+
+#[test]
+fn test_ground_outlier_map() {
+    create_dir_all("out").expect("error creating 'out' dir");
+
+    // occlude a corner of a known ground render with a foreign object
+    let mut constraints = DynTrie::new();
+    constraints.observe("cam_roll", Arc::new(0.));
+    constraints.observe("cam_y", Arc::new(1.5));
+    let trace = grounded_depth_model.generate(ObsParams::default(), constraints.clone()).0;
+    let mut observation = trace.data.read::<Depths>("observation").clone();
+    let occluded = Rect { x0: 0, y0: 0, x1: W / 4, y1: H / 4 };
+    for y in occluded.y0..occluded.y1 {
+        for x in occluded.x0..occluded.x1 {
+            observation[y * W + x] = 0.95;
+        }
+    }
+    constraints.observe("observation", Arc::new(observation.clone()));
+    let trace = grounded_depth_model.generate(ObsParams::default(), constraints).0;
+
+    let outliers = depth_outlier_map(&trace);
+    save_depths2("./out/ground_outliers.bmp", &observation, &outliers);
+    let (mut inside, mut outside) = (0.0, 0.0);
+    for y in 0..H {
+        for x in 0..W {
+            if occluded.contains(x, y) { inside += outliers[y * W + x]; } else { outside += outliers[y * W + x]; }
+        }
+    }
+    assert!(inside / occluded.area() as f32 > 0.9);
+    assert!(outside / (AREA - occluded.area()) as f32 <= 0.2);
+}
+
+#[test]
+fn test_select_pixel_noise() {
+    let mut constraints = DynTrie::new();
+    constraints.observe("observation", Arc::new(load_colors("./tests/ball.bmp")));
+    for noise in [PixelNoise::Gaussian, PixelNoise::Laplace, PixelNoise::StudentT(3.0)] {
+        let obs = ObsParams { noise, ..ObsParams::default() };
+        let (trace, weight) = ball_model.generate(obs, constraints.clone());
+        assert!(weight.is_finite());
+        assert_eq!(trace.args.noise, noise);
+    }
+}
+
+#[test]
+fn test_ground_depth_sensor() {
+    let obs = ObsParams { depth_sensor: Some(DepthSensor::default()), ..ObsParams::default() };
+    let mut constraints = DynTrie::new();
+    constraints.observe("cam_roll", Arc::new(0.));
+    constraints.observe("cam_y", Arc::new(1.5));
+    let trace = grounded_depth_model.generate(obs.clone(), constraints).0;
+    let observation = trace.data.read::<Depths>("observation").clone();
+
+    // the sky only returns spurious readings
+    assert!(observation[..W].iter().filter(|&&p| p == 0.0).count() > W * 9 / 10);
+
+    // the true camera height explains the frame much better than a nearby one
+    let weight = |cam_y: f64| {
+        let mut constraints = DynTrie::new();
+        constraints.observe("cam_roll", Arc::new(0.));
+        constraints.observe("cam_y", Arc::new(cam_y));
+        constraints.observe("observation", Arc::new(observation.clone()));
+        grounded_depth_model.generate(obs.clone(), constraints).1
+    };
+    assert!(weight(1.5) > weight(1.55) + 100.0);
+}
+
+#[test]
+fn test_masked_observation() {
+    let observation = load_colors("./tests/ball.bmp");
+    let mut constraints = DynTrie::new();
+    constraints.observe("observation", Arc::new(observation));
+
+    // an empty region of interest ignores the observation entirely
+    let obs = ObsParams { mask: Mask::Roi(Rect::default()), ..ObsParams::default() };
+    assert_eq!(ball_model.generate(obs, constraints.clone()).1, 0.0);
+
+    // while scoring only the table in the lower half still depends on it
+    let obs = ObsParams { mask: Mask::Roi(Rect { x0: 0, y0: H / 2, x1: W, y1: H }), ..ObsParams::default() };
+    let weight = ball_model.generate(obs, constraints).1;
+    assert!(weight.is_finite() && weight != 0.0);
+}
+
+#[test]
+fn test_rgbd_ball_geometry() {
+    let latents = |ball_u: f64| ball_latents([0.4, 0.5, 0.6], ball_u, [0.4, 0.5, 0.6], 0.4);
+    let trace = rgbd_ball_model.generate(ObsParams::default(), latents(0.0)).0;
+    let (colors, depths) = trace.retv.clone().unwrap();
+    assert_eq!((colors.len(), depths.len()), (AREA, AREA));
+    let observation = trace.data.read::<Rgbd>("observation").clone();
+
+    // a ball colored like the table is barely visible in color, but its depth still pins down its position
+    let obs = ObsParams { sigma: 10.0, ..ObsParams::default() };
+    let weight = |ball_u: f64| {
+        let mut constraints = latents(ball_u);
+        constraints.observe("observation", Arc::new(observation.clone()));
+        rgbd_ball_model.generate(obs.clone(), constraints).1
+    };
+    assert!(weight(0.0) > weight(0.1) + 100.0, "{} {}", weight(0.0), weight(0.1));
+}
+
+#[test]
+fn test_multi_view_settings() {
+    // the stereo and RGB-D models infer the color noise and observe edges like the single-view models
+    let obs = ObsParams { noise_prior: Some(NoisePrior::default()), features: Features::PixelsAndEdges, ..ObsParams::default() };
+    let rig = stereo_rig(0.12, PI/2.0, NEAR, FAR);
+    let trace = stereo_ball_model.generate((obs.clone(), rig.clone()), DynTrie::new()).0;
+    for addr in ["log_pixel_noise", "observation_0", "observation_1", "edges_0", "edges_1"] {
+        assert!(trace.data.search(addr).is_some(), "{}", addr);
+    }
+    let obs = ObsParams { noise_prior: Some(NoisePrior::default()), ..ObsParams::default() };
+    let trace = rgbd_ball_model.generate(obs, DynTrie::new()).0;
+    assert!(inferred_noise(&trace).is_some());
+
+    // the stereo model scores summary statistics per view and its renders instead of a plate, while the RGB-D model
+    // scores its pixels with `NoisyRgbd` whatever the color models would
+    let abc = AbcColors { stats: vec![SummaryStatistic::RegionMeans { rows: 4, cols: 4 }], kernel: AbcKernel::Gaussian, mask: Mask::All };
+    let background = Some(BackgroundPlate::new(vec![[0.5; 3]; AREA]));
+    let obs = ObsParams { background, abc: Some(abc), features: Features::Edges, ..ObsParams::default() };
+    let latents = |ball_u: f64| ball_latents([0.4, 0.5, 0.6], ball_u, [0.9, 0.3, 0.3], 0.4);
+    let weight = |ball_u: f64| {
+        let views = stereo_ball_model.generate((ObsParams::default(), rig.clone()), latents(ball_u)).0.retv.unwrap();
+        let mut constraints = latents(0.0);
+        for (i, view) in views.into_iter().enumerate() {
+            constraints.observe(&format!("observation_{i}"), Arc::new(view));
+        }
+        stereo_ball_model.generate((ObsParams { features: Features::Pixels, ..obs.clone() }, rig.clone()), constraints).1
+    };
+    assert!(weight(0.0) > weight(0.3), "{} {}", weight(0.0), weight(0.3));
+    let constraints = latents(0.0);
+    let trace = rgbd_ball_model.generate(obs, constraints).0;
+    assert!(trace.data.search("observation").is_some() && trace.data.search("edges").is_none());
+}
+
+#[test]
+fn test_coarse_to_fine() {
+    let mut constraints = DynTrie::new();
+    constraints.observe("observation", Arc::new(load_colors("./tests/ball.bmp")));
+    let trace = ball_model.generate(ObsParams { level: 3, ..ObsParams::default() }, constraints).0;
+
+    let mut ball_mask = AddrMap::new();
+    ball_mask.visit("ball_u");
+    ball_mask.visit("ball_v");
+    let mut levels = vec![];
+    let trace = coarse_to_fine(&ball_model, trace, &[3, 2, 1, 0], 5, |trace| {
+        levels.push(trace.args.level);
+        let (trace, _) = regen_mh(&ball_model, trace, &ball_mask);
+        mh(&ball_model, trace, &gaussian_drift, (vec!["ball_u", "ball_v"], 0.05)).0
+    });
+    assert_eq!(levels, [3, 3, 3, 3, 3, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0]);
+
+    // the final trace is scored at full resolution
+    let observation = trace.data.read::<Colors>("observation");
+    let w = |t: &DynTrace<ObsParams,Colors>| noisy_colors.logpdf(&observation, (t.retv.clone().unwrap(), OUTLIER_PROB, PIXEL_NOISE));
+    let rescored = ball_model.generate(ObsParams::default(), trace.data.clone()).0;
+    let prior = rescored.logjp - w(&rescored);
+    assert!((trace.logjp - prior - w(&trace)).abs() < 1e-6 * trace.logjp.abs(), "{} != {}", trace.logjp, prior + w(&trace));
+}
+
+#[test]
+fn test_edge_observation() {
+    let latents = |ball_u: f64, ball_radius: f64| ball_latents([0.2, 0.3, 0.4], ball_u, [0.9, 0.8, 0.5], ball_radius);
+    let trace = ball_model.generate(ObsParams::default(), latents(0.0, 0.4)).0;
+    assert!(trace.data.search("edges").is_none());
+
+    // under dimmer light than the model renders with, the silhouette in another render still locates the ball, on
+    // average over the render noise
+    let observation = ball_model.generate(ObsParams::default(), latents(0.0, 0.4)).0.retv.unwrap();
+    let observation = observation.iter().map(|c| vec3_scale(c, 0.7)).collect::<Colors>();
+    let obs = ObsParams { features: Features::Edges, ..ObsParams::default() };
+    let weight = |ball_u: f64, ball_radius: f64| {
+        (0..8).map(|_| {
+            let mut constraints = latents(ball_u, ball_radius);
+            constraints.observe("edges", Arc::new(edge_map(&observation)));
+            let (trace, weight) = ball_model.generate(obs.clone(), constraints);
+            assert!(trace.data.search("observation").is_none());
+            weight
+        }).sum::<f64>() / 8.0
+    };
+    let w = weight(0.0, 0.4);
+    // (the noise edges over the ball's surface favor smaller silhouettes, so only a bigger ball is ruled out)
+    for (ball_u, ball_radius) in [(0.1, 0.4), (-0.1, 0.4), (0.0, 0.45)] {
+        let alternative = weight(ball_u, ball_radius);
+        assert!(w > alternative, "{} {}", w, alternative);
+    }
+}
+
+#[test]
+fn test_infer_pixel_noise() {
+    let mut camera = DynTrie::new();
+    camera.observe("cam_roll", Arc::new(0.));
+    camera.observe("cam_y", Arc::new(1.5));
+    let mut noise_mask = AddrMap::new();
+    noise_mask.visit("outlier_prob");
+    noise_mask.visit("log_pixel_noise");
+
+    // the posterior noise follows how noisy the observation is, given the true camera
+    let obs = ObsParams { noise_prior: Some(NoisePrior::default()), ..ObsParams::default() };
+    let mut inferred = vec![];
+    for (outlier_prob, sigma) in [(0.01, 0.02), (0.2, 0.3)] {
+        let synth = ObsParams { depth_outlier_prob: outlier_prob, depth_sigma: sigma, ..ObsParams::default() };
+        let synth_trace = grounded_depth_model.generate(synth, camera.clone()).0;
+        assert_eq!(inferred_noise(&synth_trace), None);
+
+        let mut constraints = camera.clone();
+        constraints.observe("observation", Arc::new(synth_trace.data.read::<Depths>("observation").clone()));
+        let mut trace = grounded_depth_model.generate(obs.clone(), constraints).0;
+        for _ in 0..300 {
+            trace = regen_mh(&grounded_depth_model, trace, &noise_mask).0;
+        }
+        inferred.push(inferred_noise(&trace).unwrap());
+    }
+    assert!(inferred[0].1 < 0.05 && inferred[1].1 > 0.15, "{:?}", inferred);
+    assert!(inferred[0].0 < inferred[1].0, "{:?}", inferred);
+}
+
+#[test]
+fn test_log_likelihood_heatmaps() {
+    let mut constraints = DynTrie::new();
+    for (addr, value) in [("cam_y", 1.2), ("cam_yaw", 0.), ("ball_u", 0.0), ("ball_v", -0.5), ("ball_radius", 0.4)] {
+        constraints.observe(addr, Arc::new(value));
+    }
+    constraints.observe("observation", Arc::new(load_colors("./tests/ball.bmp")));
+
+    // with a render cache that tolerates faint shadows, moving the ball only re-renders, and so only rescores, the
+    // pixels around it
+    let mut moved = DynTrie::new();
+    moved.observe("ball_u", Arc::new(0.2));
+    let (trace, proposed) = with_render_cache(0.5, || {
+        let trace = ball_model.generate(ObsParams::default(), constraints).0;
+        let proposed = ball_model.update(trace.clone(), ObsParams::default(), ArgDiff::NoChange, moved).0;
+        (trace, proposed)
+    });
+    let observation = trace.data.read::<Colors>("observation").clone();
+    let render = trace.retv.clone().unwrap();
+    let map = color_log_likelihood_map(&trace).unwrap();
+    let expected = noisy_colors.logpdf(&observation, (render.clone(), OUTLIER_PROB, PIXEL_NOISE));
+    assert!((map.iter().sum::<f64>() - expected).abs() < 1e-6 * expected.abs());
+
+    let diff = log_likelihood_diff(&color_log_likelihood_map(&proposed).unwrap(), &map);
+    let changed = diff.iter().filter(|d| **d != 0.0).count();
+    assert!(0 < changed && changed < AREA / 4, "{changed}");
+
+    let colors = log_likelihood_colors(&map);
+    let best = map.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    assert_eq!(colors[map.iter().position(|w| *w == best).unwrap()], [0.0; 3]);
+    let diff_colors = log_likelihood_diff_colors(&diff);
+    assert!(diff.iter().zip(diff_colors.iter()).all(|(d, c)| *d != 0.0 || *c == [1.0; 3]));
+    assert!(diff_colors.contains(&[0.0, 0.0, 1.0]) || diff_colors.contains(&[1.0, 0.0, 0.0]));
+
+    let dir = std::env::temp_dir();
+    let path = dir.join("ball_log_likelihood.bmp");
+    save_log_likelihood(path.to_str().unwrap(), &map);
+    let saved = load_colors(path.to_str().unwrap());
+    assert!(saved.iter().zip(colors.iter()).all(|(a, b)| (0..=2).all(|i| (a[i] - b[i]).abs() <= 1.0 / 255.0)));
+    let path = dir.join("ball_log_likelihood_diff.bmp");
+    save_log_likelihood_diff(path.to_str().unwrap(), &diff);
+    let path = dir.join("ball_log_likelihood3.bmp");
+    save_colors2_log_likelihood(path.to_str().unwrap(), &observation, &render, &map);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), std::fs::metadata(dir.join("ball_log_likelihood.bmp")).unwrap().len() * 3 - 2 * 54);
+}
+
+#[test]
+fn test_feature_log_likelihood_maps() {
+    let observation = load_colors("./tests/ball.bmp");
+    let trace = |obs: &ObsParams| {
+        let mut constraints = DynTrie::new();
+        if obs.features != Features::Edges {
+            constraints.observe("observation", Arc::new(observation.clone()));
+        }
+        if obs.features != Features::Pixels {
+            constraints.observe("edges", Arc::new(edge_map(&observation)));
+        }
+        ball_model.generate(obs.clone(), constraints).0
+    };
+
+    // the maps of edges and of coarse pyramid levels sum to the log-likelihood, the joint minus the prior
+    for (features, level) in [(Features::Edges, 0), (Features::PixelsAndEdges, 2), (Features::Pixels, 3)] {
+        let obs = ObsParams { features, level, ..ObsParams::default() };
+        let trace = trace(&obs);
+        let prior = rescore(&ball_model, trace.clone(), ObsParams { inv_temp: 0.0, ..obs.clone() }).logjp;
+        let map = color_log_likelihood_map(&trace).unwrap();
+        let w = trace.logjp - prior;
+        assert!((map.iter().sum::<f64>() - w).abs() < 1e-6 * w.abs(), "{:?} {}", features, level);
+        assert_eq!(color_outlier_map(&trace).is_some(), features != Features::Edges);
+    }
+
+    // summary statistics don't split into pixels
+    let stats = vec![SummaryStatistic::RegionMeans { rows: 4, cols: 4 }];
+    let abc = AbcColors { stats, kernel: AbcKernel::Gaussian, mask: Mask::All };
+    let trace = trace(&ObsParams { abc: Some(abc), ..ObsParams::default() });
+    assert!(color_log_likelihood_map(&trace).is_none() && color_outlier_map(&trace).is_none());
+}
+
+#[test]
+fn test_background_plate() {
+    let latents = |ball_u: f64| ball_latents([0.4, 0.5, 0.6], ball_u, [0.9, 0.3, 0.3], 0.4);
+
+    // a cluttered room that the table and sky of the model can't explain, with the ball composited in
+    let clutter = (0..AREA).map(|i| {
+        let (x, y) = ((i % W) as f32, (i / W) as f32);
+        [0.5 + 0.4 * (0.3 * x).sin(), 0.5 + 0.4 * (0.2 * y).cos(), 0.5 + 0.4 * (0.1 * (x + y)).sin()]
+    }).collect::<Colors>();
+    let render = ball_model.generate(ObsParams::default(), latents(0.0)).0.retv.unwrap();
+    let x = vec3_euler_to_pose([0.0, 1.2, 1.2], [0.0, 0.0, 0.0]);
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene: Scene = vec![
+        (Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }), [0.4, 0.5, 0.6]),
+        (Box::new(Sphere { center: [0.0, 0.4, -0.5], radius: 0.4 }), [0.9, 0.3, 0.3])
+    ];
+    let ball = raytrace_coverage(x, &camera, &scene, &[false, true]);
+    assert!(ball.count() > 100);
+    let observation = (0..AREA).map(|i| if ball.contains(i % W, i / W) { render[i] } else { clutter[i] }).collect::<Colors>();
+
+    let obs = ObsParams { background: Some(BackgroundPlate::new(clutter)), ..ObsParams::default() };
+    let weight = |ball_u: f64| {
+        let mut constraints = latents(ball_u);
+        constraints.observe("observation", Arc::new(observation.clone()));
+        ball_model.generate(obs.clone(), constraints).1
+    };
+    let w = weight(0.0);
+    for ball_u in [-0.2, -0.1, 0.1, 0.2] {
+        assert!(w > weight(ball_u) + 100.0, "{} {}", w, weight(ball_u));
+    }
+
+    // the maps score against the plate around the coverage of the traced ball, with or without a render cache
+    for tolerance in [None, Some(0.0)] {
+        let mut constraints = latents(0.0);
+        constraints.observe("observation", Arc::new(observation.clone()));
+        let generate = || {
+            let trace = ball_model.generate(obs.clone(), constraints).0;
+            ball_model.generate(obs.clone(), latents(0.2));
+            trace
+        };
+        let trace = match tolerance {
+            Some(tolerance) => with_render_cache(tolerance, generate),
+            None => generate()
+        };
+        let map = color_log_likelihood_map(&trace).unwrap();
+        let outliers = color_outlier_map(&trace).unwrap();
+        assert!(outliers.iter().filter(|&&p| p > 0.5).count() < ball.count() / 10);
+        let w = trace.logjp - rescore(&ball_model, trace.clone(), ObsParams { inv_temp: 0.0, ..obs.clone() }).logjp;
+        assert!((map.iter().sum::<f64>() - w).abs() < 1e-6 * w.abs(), "{} {}", map.iter().sum::<f64>(), w);
+    }
+
+    // and around that of the traced object of the other color models
+    let cuboid = [("cuboid_half_x", 0.3), ("cuboid_half_y", 0.4), ("cuboid_half_z", 0.2), ("cuboid_yaw", 0.3)];
+    let shape = shape_model.generate(obs.clone(), shape_latents(ShapeClass::Cuboid, &cuboid)).0;
+    let sphere = sphere_color_model.generate(obs.clone(), DynTrie::new()).0;
+    for (model, trace) in [(shape_model, shape), (sphere_color_model, sphere)] {
+        let map = color_log_likelihood_map(&trace).unwrap();
+        let w = trace.logjp - rescore(&model, trace.clone(), ObsParams { inv_temp: 0.0, ..obs.clone() }).logjp;
+        assert!((map.iter().sum::<f64>() - w).abs() < 1e-6 * w.abs(), "{} {}", map.iter().sum::<f64>(), w);
+    }
+}
+
+#[test]
+fn test_abc_observation() {
+    let latents = |ball_u: f64| ball_latents([0.2, 0.3, 0.4], ball_u, [0.9, 0.8, 0.5], 0.4);
+    let render = ball_model.generate(ObsParams::default(), latents(0.0)).0.retv.unwrap();
+
+    // under heavy zero-mean noise, the mean colors of image regions still locate the ball
+    let mut rng = ThreadRng::default();
+    let observation = render.iter().map(|c| c.map(|v| v + normal.random(&mut rng, (0.0, 0.3)) as f32)).collect::<Colors>();
+    let abc = AbcColors { stats: vec![SummaryStatistic::RegionMeans { rows: 8, cols: 8 }], kernel: AbcKernel::Gaussian, mask: Mask::All };
+    let obs = ObsParams { abc: Some(abc), abc_tolerance: 0.02, ..ObsParams::default() };
+    let weight = |ball_u: f64| {
+        let mut constraints = latents(ball_u);
+        constraints.observe("observation", Arc::new(observation.clone()));
+        ball_model.generate(obs.clone(), constraints).1
+    };
+    let w = weight(0.0);
+    assert!(w.is_finite());
+    for ball_u in [-0.2, -0.1, 0.1, 0.2] {
+        assert!(w > weight(ball_u), "{} {}", w, weight(ball_u));
+    }
+}
+
+#[test]
+fn test_infer_cam_rotation() {
+    let latents = |cam_rotation: Quat| {
+        let mut constraints = ball_latents([0.2, 0.3, 0.4], 0.0, [0.9, 0.8, 0.5], 0.4);
+        constraints.remove("cam_yaw");
+        constraints.observe("cam_rotation", Arc::new(cam_rotation));
+        constraints
+    };
+    let obs = ObsParams { cam_rotation: Some(IsotropicSO3::concentration(0.3)), ..ObsParams::default() };
+
+    // a camera tilted about all three axes at once
+    let truth = quat_from_rotvec([0.15, -0.1, 0.2]);
+    let synth_trace = ball_model.generate(obs.clone(), latents(truth)).0;
+    assert!(synth_trace.data.search("cam_yaw").is_none());
+    let mut constraints = latents(quat_id());
+    constraints.observe("observation", Arc::new(synth_trace.retv.unwrap()));
+    let mut trace = ball_model.generate(obs, constraints).0;
+
+    let angle_error = |trace: &DynTrace<ObsParams,Colors>| {
+        vec3_norm(&quat_to_rotvec(quat_mul(quat_conj(truth), trace.data.read::<Quat>("cam_rotation"))))
+    };
+    let initial_error = angle_error(&trace);
+    for _ in 0..200 {
+        trace = mh(&ball_model, trace, &rotation_drift, (vec!["cam_rotation"], 0.05)).0;
+    }
+    assert!(angle_error(&trace) < 0.3 * initial_error, "{} {}", initial_error, angle_error(&trace));
+}
+
+dyngen!(
+fn posed_camera_model(obs: ObsParams) -> Colors {
+    // the whole camera pose as one random choice, looking at spheres at several depths whose parallax helps
+    // tell translations from rotations
+    let x = gaussian_pose([0.0, 1.2, 1.2, 0.0, 0.0, 0.0, 1.0], 0.2, IsotropicSO3::concentration(0.2)) %= "cam_pose";
+
+    let scene: Scene = vec![
+        (Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }), [0.2, 0.3, 0.4]),
+        (Box::new(Sphere { center: [0.0, 0.4, -0.5], radius: 0.4 }), [0.9, 0.8, 0.5]),
+        (Box::new(Sphere { center: [-0.4, 0.8, 0.5], radius: 0.15 }), [0.2, 0.9, 0.3]),
+        (Box::new(Sphere { center: [0.8, 0.5, -1.5], radius: 0.5 }), [0.3, 0.2, 0.9])
+    ];
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let mut pixels = vec![[0.0; 3]; AREA];
+    raytrace_colors(x, &camera, &scene, [0.8, 0.9, 0.9], &mut pixels);
+    noisy_colors(pixels.clone(), obs.outlier_prob, obs.sigma) %= "observation";
+
+    pixels
+});
+
+#[test]
+fn test_infer_cam_pose() {
+    let truth_q = quat_from_rotvec([0.05, -0.03, 0.05]);
+    let truth: Pose = [0.05, 1.15, 1.25, truth_q[0], truth_q[1], truth_q[2], truth_q[3]];
+    let mut synth_constraints = DynTrie::new();
+    synth_constraints.observe("cam_pose", Arc::new(truth));
+    let synth_trace = posed_camera_model.generate(ObsParams::default(), synth_constraints).0;
+
+    let start: Pose = [0.0, 1.2, 1.2, 0.0, 0.0, 0.0, 1.0];
+    let mut constraints = DynTrie::new();
+    constraints.observe("cam_pose", Arc::new(start));
+    constraints.observe("observation", Arc::new(synth_trace.retv.unwrap()));
+    let obs = ObsParams { sigma: 0.2, ..ObsParams::default() };
+    let mut trace = posed_camera_model.generate(obs, constraints).0;
+
+    let angle_error = |trace: &DynTrace<ObsParams,Colors>| {
+        vec3_norm(&quat_to_rotvec(quat_mul(quat_conj(truth_q), pose_to_quat(trace.data.read::<Pose>("cam_pose")))))
+    };
+    let (initial_error, initial_logjp) = (angle_error(&trace), trace.logjp);
+    for stdev in [0.02, 0.01, 0.005] {
+        for _ in 0..70 {
+            // moves of the position, the orientation, and both along the ridge where they trade off
+            trace = mh(&posed_camera_model, trace, &pose_drift, (vec!["cam_pose"], stdev, 1e-3)).0;
+            trace = mh(&posed_camera_model, trace, &pose_drift, (vec!["cam_pose"], 1e-3, stdev)).0;
+            trace = mh(&posed_camera_model, trace, &pose_drift, (vec!["cam_pose"], stdev, stdev)).0;
+        }
+    }
+    // along that ridge of near-equal images the position stays loosely determined, but the orientation is pinned down
+    assert!(trace.logjp > initial_logjp);
+    assert!(angle_error(&trace) < 0.6 * initial_error, "{} {}", initial_error, angle_error(&trace));
+}
+
+fn shape_latents(class: ShapeClass, sizes: &[(&str, f64)]) -> DynTrie {
+    let mut constraints = DynTrie::new();
+    for (addr, value) in [("cam_y", 1.2), ("cam_yaw", 0.), ("ambient_brightness", 0.9), ("table_c0", 0.2),
+                          ("table_c1", 0.3), ("table_c2", 0.4), ("object_u", 0.1), ("object_v", -0.5),
+                          ("object_c0", 0.9), ("object_c1", 0.8), ("object_c2", 0.5)] {
+        constraints.observe(addr, Arc::new(value));
+    }
+    for &(addr, value) in sizes {
+        constraints.observe(addr, Arc::new(value));
+    }
+    constraints.observe("shape", Arc::new(class.index()));
+    constraints
+}
+
+#[test]
+fn test_shape_class() {
+    let cuboid = [("cuboid_half_x", 0.3), ("cuboid_half_y", 0.35), ("cuboid_half_z", 0.3), ("cuboid_yaw", 0.4)];
+    let synth_trace = shape_model.generate(ObsParams::default(), shape_latents(ShapeClass::Cuboid, &cuboid)).0;
+    assert!(synth_trace.data.search("sphere_radius").is_none());
+    assert!(synth_trace.data.search("cylinder_radius").is_none());
+    let observation = synth_trace.retv.unwrap();
+
+    // of objects of about the same size at the same place, the one of the true class explains the image best
+    let weight = |class: ShapeClass, sizes: &[(&str, f64)]| {
+        let mut constraints = shape_latents(class, sizes);
+        constraints.observe("observation", Arc::new(observation.clone()));
+        shape_model.generate(ObsParams::default(), constraints).1
+    };
+    let w = weight(ShapeClass::Cuboid, &cuboid);
+    assert!(w.is_finite());
+    let sphere = weight(ShapeClass::Sphere, &[("sphere_radius", 0.35)]);
+    let cylinder = weight(ShapeClass::Cylinder, &[("cylinder_radius", 0.33), ("cylinder_half_height", 0.35)]);
+    assert!(w > sphere && w > cylinder, "{} {} {}", w, sphere, cylinder);
+
+    // and an index of no class is impossible, against the image or against a background plate
+    assert_eq!(ShapeClass::try_from(3), Err(3));
+    let obs = ObsParams { background: Some(BackgroundPlate::new(observation.clone())), ..ObsParams::default() };
+    for (shape, obs) in [(3_i64, ObsParams::default()), (-1, obs)] {
+        let mut constraints = shape_latents(ShapeClass::Sphere, &[]);
+        constraints.remove("shape");
+        constraints.observe("shape", Arc::new(shape));
+        constraints.observe("observation", Arc::new(observation.clone()));
+        let (trace, weight) = shape_model.generate(obs, constraints);
+        assert_eq!(weight, f64::NEG_INFINITY);
+        assert!(color_log_likelihood_map(&trace).is_some());
+    }
+}
+
+#[test]
+fn test_infer_shape_class() {
+    let cylinder = [("cylinder_radius", 0.3), ("cylinder_half_height", 0.45)];
+    let synth_trace = shape_model.generate(ObsParams::default(), shape_latents(ShapeClass::Cylinder, &cylinder)).0;
+    let mut constraints = shape_latents(ShapeClass::Sphere, &[("sphere_radius", 0.35)]);
+    constraints.observe("observation", Arc::new(synth_trace.retv.unwrap()));
+    let mut trace = shape_model.generate(ObsParams::default(), constraints).0;
+
+    for _ in 0..120 {
+        trace = mh(&shape_model, trace, &shape_switch, 0.05).0;
+        let sizes = ShapeClass::try_from(trace.data.read::<i64>("shape")).unwrap().size_addrs().to_vec();
+        trace = mh(&shape_model, trace, &gaussian_drift, (sizes, 0.02)).0;
+    }
+
+    // switching the class keeps the object where it was, and only its sizes change
+    assert_eq!(ShapeClass::try_from(trace.data.read::<i64>("shape")), Ok(ShapeClass::Cylinder));
+    assert_eq!(trace.data.read::<f64>("object_u"), 0.1);
+    assert_eq!(trace.data.read::<f64>("object_v"), -0.5);
+    assert!(trace.data.search("sphere_radius").is_none());
+    assert!((trace.data.read::<f64>("cylinder_half_height") - 0.45).abs() < 0.05);
+}
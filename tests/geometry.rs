@@ -0,0 +1,190 @@
+use modppl::prelude::*;
+use modppl_derender::*;
+use std::f32::consts::PI;
+
+
+/// integrates `exp(logpdf)` over the unit sphere with the midpoint rule in `z` and the azimuth
+fn integrate_s2(logpdf: impl Fn(&Vec3) -> f64, n: usize) -> f64 {
+    let (hz, hphi) = (2.0 / n as f64, 2.0 * PI as f64 / n as f64);
+    let mut total = 0.0;
+    for i in 0..n {
+        let z = -1.0 + (i as f64 + 0.5) * hz;
+        let r = (1.0 - z * z).sqrt();
+        for j in 0..n {
+            let phi = (j as f64 + 0.5) * hphi;
+            total += logpdf(&[(r * phi.cos()) as f32, (r * phi.sin()) as f32, z as f32]).exp() * hz * hphi;
+        }
+    }
+    total
+}
+
+fn mean_cosine(samples: &[Vec3], axis: Vec3) -> f32 {
+    samples.iter().map(|x| vec3_dot(x, &axis)).sum::<f32>() / samples.len() as f32
+}
+
+#[test]
+fn test_tangent_frame() {
+    for mut n in [[0.0, 0.0, 1.0], [0.0, 0.0, -1.0], [1.0, 2.0, -3.0], [0.3, -0.2, 0.01]] {
+        vec3_normalize(&mut n);
+        let (t, b) = tangent_frame(n);
+        for (u, v) in [(n, t), (n, b), (t, b)] {
+            assert!(vec3_dot(&u, &v).abs() < 1e-5);
+        }
+        assert!((vec3_norm(&t) - 1.0).abs() < 1e-5 && (vec3_norm(&b) - 1.0).abs() < 1e-5);
+        let c = vec3_cross(&t, &b);
+        assert!(vec3_dot(&c, &n) > 0.999);
+    }
+}
+
+#[test]
+fn test_sphere_densities_normalized() {
+    let mut mu = [0.3, -0.5, 0.8];
+    vec3_normalize(&mut mu);
+    let n = 1000;
+    let z = integrate_s2(|x| uniform_s2.logpdf(x, ()), n);
+    assert!((z - 1.0).abs() < 1e-3, "{}", z);
+    for kappa in [0.0, 1.0, 10.0, 50.0] {
+        let z = integrate_s2(|x| von_mises_fisher.logpdf(x, (mu, kappa)), n);
+        assert!((z - 1.0).abs() < 1e-2, "kappa {}: {}", kappa, z);
+    }
+    let z = integrate_s2(|x| uniform_hemisphere.logpdf(x, mu), n);
+    assert!((z - 1.0).abs() < 1e-2, "{}", z);
+    let z = integrate_s2(|x| cosine_hemisphere.logpdf(x, mu), n);
+    assert!((z - 1.0).abs() < 1e-2, "{}", z);
+
+    // directions off the sphere or the hemisphere have no density
+    assert_eq!(uniform_s2.logpdf(&[0.5, 0.0, 0.0], ()), f64::NEG_INFINITY);
+    assert_eq!(von_mises_fisher.logpdf(&[2.0, 0.0, 0.0], (mu, 1.0)), f64::NEG_INFINITY);
+    assert_eq!(uniform_hemisphere.logpdf(&vec3_scale(&mu, -1.0), mu), f64::NEG_INFINITY);
+    assert_eq!(cosine_hemisphere.logpdf(&vec3_scale(&mu, -1.0), mu), f64::NEG_INFINITY);
+}
+
+#[test]
+fn test_sphere_samples() {
+    let mut rng = ThreadRng::default();
+    let mut mu = [-0.2, 0.9, 0.1];
+    vec3_normalize(&mut mu);
+    let n = 20_000;
+    let is_unit = |x: &Vec3| (vec3_norm(x) - 1.0).abs() < 1e-4;
+
+    let samples = (0..n).map(|_| uniform_s2.random(&mut rng, ())).collect::<Vec<Vec3>>();
+    assert!(samples.iter().all(is_unit));
+    assert!(mean_cosine(&samples, mu).abs() < 0.02);
+
+    // the mean resultant length of the von Mises-Fisher distribution is coth(kappa) - 1 / kappa
+    for kappa in [0.5f32, 5.0, 100.0] {
+        let samples = (0..n).map(|_| von_mises_fisher.random(&mut rng, (mu, kappa))).collect::<Vec<Vec3>>();
+        assert!(samples.iter().all(is_unit));
+        let expected = 1.0 / kappa.tanh() - 1.0 / kappa;
+        assert!((mean_cosine(&samples, mu) - expected).abs() < 0.02, "kappa {}", kappa);
+        let (t, _) = tangent_frame(mu);
+        assert!(mean_cosine(&samples, t).abs() < 0.02);
+    }
+
+    // cosines to the axis are uniform, or have density 2 cos(theta) when cosine-weighted
+    let samples = (0..n).map(|_| uniform_hemisphere.random(&mut rng, mu)).collect::<Vec<Vec3>>();
+    assert!(samples.iter().all(|x| is_unit(x) && vec3_dot(x, &mu) >= 0.0));
+    assert!((mean_cosine(&samples, mu) - 0.5).abs() < 0.01);
+    let samples = (0..n).map(|_| cosine_hemisphere.random(&mut rng, mu)).collect::<Vec<Vec3>>();
+    assert!(samples.iter().all(|x| is_unit(x) && vec3_dot(x, &mu) >= 0.0));
+    assert!((mean_cosine(&samples, mu) - 2.0 / 3.0).abs() < 0.01);
+}
+
+#[test]
+fn test_rotation_vectors() {
+    for v in [[0.0, 0.0, 0.0], [1e-4, 0.0, -2e-4], [0.3, -0.2, 0.5], [0.0, 3.0, 0.0]] {
+        let q = quat_from_rotvec(v);
+        assert!((quat_norm(&q) - 1.0).abs() < 1e-6);
+        let w = quat_to_rotvec(q);
+        assert!((0..=2).all(|i| (v[i] - w[i]).abs() < 1e-4), "{:?} != {:?}", v, w);
+        assert_eq!(quat_to_rotvec(vec4_scale(&q, -1.0)), w);
+    }
+    let q = euler_xyz([0.3, 0.0, 0.0]);
+    let w = quat_to_rotvec(q);
+    assert!((w[0] - 0.3).abs() < 1e-5 && w[1].abs() < 1e-5 && w[2].abs() < 1e-5);
+    let v = [0.0, 1.0, 0.0];
+    let rotated = quat_rotatev(quat_mul(quat_conj(q), q), v);
+    assert!((0..=2).all(|i| (rotated[i] - v[i]).abs() < 1e-5));
+}
+
+#[test]
+fn test_rotation_distributions() {
+    let mut rng = ThreadRng::default();
+    let mean = quat_from_rotvec([0.4, -1.0, 0.2]);
+
+    // the densities depend only on the angle to the mean on the 3-sphere, whose area element is 4 pi sin^2
+    for kappa in [0.0, 1.0, 10.0, 200.0] {
+        let n = 100_000;
+        let h = PI as f64 / n as f64;
+        let z = (0..n).map(|i| {
+            let alpha = (i as f64 + 0.5) * h;
+            let q = quat_mul(mean, [alpha.sin() as f32, 0.0, 0.0, alpha.cos() as f32]);
+            isotropic_so3.logpdf(&q, (mean, kappa)).exp() * 4.0 * PI as f64 * alpha.sin().powi(2) * h
+        }).sum::<f64>();
+        assert!((z - 1.0).abs() < 1e-3, "kappa {}: {}", kappa, z);
+    }
+    assert_eq!(isotropic_so3.logpdf(&mean, (mean, 5.0)), isotropic_so3.logpdf(&vec4_scale(&mean, -1.0), (mean, 5.0)));
+    assert_eq!(uniform_so3.logpdf(&mean, ()), isotropic_so3.logpdf(&mean, (mean, 0.0)));
+    assert_eq!(uniform_so3.logpdf(&[0.5, 0.0, 0.0, 0.5], ()), f64::NEG_INFINITY);
+
+    // uniform rotations take any vector anywhere on the sphere
+    let n = 20_000;
+    let samples = (0..n).map(|_| uniform_so3.random(&mut rng, ())).collect::<Vec<Quat>>();
+    assert!(samples.iter().all(|q| (quat_norm(q) - 1.0).abs() < 1e-5));
+    let rotated = samples.iter().map(|q| quat_rotatev(*q, [0.0, 0.0, 1.0])).collect::<Vec<Vec3>>();
+    assert!(mean_cosine(&rotated, [0.0, 0.0, 1.0]).abs() < 0.02 && mean_cosine(&rotated, [1.0, 0.0, 0.0]).abs() < 0.02);
+
+    // samples match the density in their angle to the mean, on either side of the sampler's switch at kappa 10
+    for kappa in [2.0, 50.0] {
+        let m = 10_000;
+        let h = PI as f64 / m as f64;
+        let expected = (0..m).map(|i| {
+            let alpha = (i as f64 + 0.5) * h;
+            let q = quat_mul(mean, [alpha.sin() as f32, 0.0, 0.0, alpha.cos() as f32]);
+            alpha.cos().abs() * isotropic_so3.logpdf(&q, (mean, kappa)).exp() * 4.0 * PI as f64 * alpha.sin().powi(2) * h
+        }).sum::<f64>();
+        let mean_cos = (0..n).map(|_| quat_dot(&mean, &isotropic_so3.random(&mut rng, (mean, kappa))).abs() as f64).sum::<f64>() / n as f64;
+        assert!((mean_cos - expected).abs() < 0.01, "kappa {}: {} != {}", kappa, mean_cos, expected);
+    }
+
+    // concentrated rotations are about Gaussian rotation vectors from the mean
+    let stdev = 0.1;
+    let kappa = IsotropicSO3::concentration(stdev);
+    let deltas = (0..n)
+        .map(|_| quat_to_rotvec(quat_mul(quat_conj(mean), isotropic_so3.random(&mut rng, (mean, kappa)))))
+        .collect::<Vec<Vec3>>();
+    for i in 0..=2 {
+        let m = deltas.iter().map(|d| d[i]).sum::<f32>() / n as f32;
+        let var = deltas.iter().map(|d| d[i] * d[i]).sum::<f32>() / n as f32;
+        assert!(m.abs() < 0.005 && (var.sqrt() - stdev).abs() < 0.005, "{} {}", m, var.sqrt());
+    }
+}
+
+#[test]
+fn test_pose_distributions() {
+    let mut rng = ThreadRng::default();
+    let q = quat_from_rotvec([0.1, 0.2, -0.3]);
+    let mean = [1.0, 2.0, -1.0, q[0], q[1], q[2], q[3]];
+    let kappa = IsotropicSO3::concentration(0.1);
+
+    // the position and orientation are independent
+    let x = gaussian_pose.random(&mut rng, (mean, 0.5, kappa));
+    let w_position: f64 = (0..=2).map(|i| normal.logpdf(&(x[i] as f64), (mean[i] as f64, 0.5))).sum();
+    let w_rotation = isotropic_so3.logpdf(&pose_to_quat(x), (q, kappa));
+    assert!((gaussian_pose.logpdf(&x, (mean, 0.5, kappa)) - w_position - w_rotation).abs() < 1e-9);
+
+    let n = 20_000;
+    let samples = (0..n).map(|_| gaussian_pose.random(&mut rng, (mean, 0.5, kappa))).collect::<Vec<Pose>>();
+    for i in 0..=2 {
+        let m = samples.iter().map(|x| x[i]).sum::<f32>() / n as f32;
+        assert!((m - mean[i]).abs() < 0.02, "{} {}", m, mean[i]);
+    }
+    assert!(samples.iter().all(|x| quat_dot(&pose_to_quat(*x), &q).abs() > 0.9));
+
+    let (lo, hi) = ([-1.0, 0.0, -2.0], [1.0, 0.5, 0.0]);
+    let samples = (0..n).map(|_| uniform_pose.random(&mut rng, (lo, hi))).collect::<Vec<Pose>>();
+    assert!(samples.iter().all(|x| (0..=2).all(|i| lo[i] <= x[i] && x[i] <= hi[i])));
+    let expected = -(2.0f64 * 0.5 * 2.0).ln() - (2.0 * std::f64::consts::PI.powi(2)).ln();
+    assert!((uniform_pose.logpdf(&samples[0], (lo, hi)) - expected).abs() < 1e-9);
+    assert_eq!(uniform_pose.logpdf(&[0.0, 1.0, -1.0, 0.0, 0.0, 0.0, 1.0], (lo, hi)), f64::NEG_INFINITY);
+}
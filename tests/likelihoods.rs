@@ -0,0 +1,475 @@
+use modppl::prelude::*;
+use modppl_derender::*;
+use std::f32::consts::PI;
+
+
+/// integrates `exp(logpdf)` over [a, b] with the midpoint rule
+fn integrate(logpdf: impl Fn(f32) -> f64, a: f32, b: f32, n: usize) -> f64 {
+    let h = (b - a) as f64 / n as f64;
+    (0..n).map(|i| logpdf(a + ((i as f64 + 0.5) * h) as f32).exp() * h).sum()
+}
+
+#[test]
+fn test_noisy_pixel_normalized() {
+    for &(mu, outlier_prob, sigma) in [(0.5, 0.1, 0.1), (0.02, 0.3, 0.05), (0.9, 0.01, 0.5), (1.0, 0.5, 0.01)].iter() {
+        let z = integrate(|x| noisy_pixel.logpdf(&x, (mu, outlier_prob, sigma)), 0.0, 1.0, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "integral {} != 1 for {:?}", z, (mu, outlier_prob, sigma));
+        assert_eq!(noisy_pixel.logpdf(&1.5, (mu, outlier_prob, sigma)), f64::NEG_INFINITY);
+    }
+}
+
+#[test]
+fn test_noisy_pixel_matches_samples() {
+    let mut rng = ThreadRng::default();
+    const N: usize = 100_000;
+    const BINS: usize = 10;
+    for &(mu, outlier_prob, sigma) in [(0.5, 0.1, 0.1), (0.05, 0.3, 0.2)].iter() {
+        let mut counts = [0usize; BINS];
+        for _ in 0..N {
+            let x = noisy_pixel.random(&mut rng, (mu, outlier_prob, sigma));
+            counts[((x * BINS as f32) as usize).min(BINS - 1)] += 1;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            let (a, b) = (i as f32 / BINS as f32, (i + 1) as f32 / BINS as f32);
+            let p = integrate(|x| noisy_pixel.logpdf(&x, (mu, outlier_prob, sigma)), a, b, 10_000);
+            let stderr = (N as f64 * p * (1. - p)).sqrt();
+            assert!((count as f64 - N as f64 * p).abs() < 5. * stderr + 1.,
+                "bin {}: {} samples, expected {}", i, count, N as f64 * p);
+        }
+    }
+}
+
+#[test]
+fn test_noisy_images_sum_pixels() {
+    let mut rng = ThreadRng::default();
+    let pixels = (0..AREA).map(|i| (i % 7) as f32 / 7.0).collect::<Depths>();
+    let noisy = noisy_depths.random(&mut rng, (pixels.clone(), 0.1, 0.05));
+    let expected = pixels.iter().zip(noisy.iter())
+        .map(|(p, q)| noisy_pixel.logpdf(q, (*p, 0.1, 0.05)))
+        .sum::<f64>();
+    assert!((noisy_depths.logpdf(&noisy, (pixels, 0.1, 0.05)) - expected).abs() < 1e-6 * expected.abs());
+
+    // outliers are scored by the uniform component, so each corrupted channel costs at most
+    // the log-ratio of the best inlier density to the outlier density
+    let colors = vec![[0.5; 3]; AREA];
+    let mut corrupted = colors.clone();
+    corrupted[0] = [0.0, 1.0, 0.0];
+    let w = noisy_colors.logpdf(&colors, (colors.clone(), 0.1, 0.05));
+    let w_corrupted = noisy_colors.logpdf(&corrupted, (colors.clone(), 0.1, 0.05));
+    assert!(w - w_corrupted < 3. * (noisy_pixel.logpdf(&0.5, (0.5, 0.1, 0.05)) - 0.1f64.ln()) + 1e-6);
+}
+
+#[test]
+fn test_truncated_normal_extremes() {
+    // a mean far outside the support leaves an exponential tail, which must stay normalized
+    let (mu, sigma) = (50.0f32, 0.1f32);
+    let z = integrate(|x| truncated_normal.logpdf(&x, (mu, sigma, 0.0, 1.0)), 0.0, 1.0, 200_000);
+    assert!((z - 1.0).abs() < 1e-3, "integral {} != 1", z);
+    let rate = (mu as f64 - 1.0) / (sigma as f64 * sigma as f64);
+    let w = truncated_normal.logpdf(&1.0, (mu, sigma, 0.0, 1.0));
+    assert!((w - rate.ln()).abs() < 1e-2, "{} != {}", w, rate.ln());
+    let w = truncated_normal.logpdf(&1.0, (-1e4, sigma, 0.0, 1.0));
+    assert!(w.is_finite() && w < 0.0);
+
+    // very narrow and very wide Gaussians are left as a spike and a flat density
+    let sigma = 1e-4f32;
+    let w = truncated_normal.logpdf(&0.5, (0.5, sigma, 0.0, 1.0));
+    let expected = -(sigma as f64 * (2. * std::f64::consts::PI).sqrt()).ln();
+    assert!((w - expected).abs() < 1e-6, "{} != {}", w, expected);
+    assert!(truncated_normal.logpdf(&0.3, (0.5, 1e4, 0.0, 1.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_truncated_normal_samples() {
+    let mut rng = ThreadRng::default();
+    const N: usize = 20_000;
+    for &(mu, sigma) in [(0.5, 0.1), (0.3, 5.0), (1.5, 0.2), (-0.4, 0.05), (50.0, 0.1), (-1e4, 1.0), (0.7, 0.0)].iter() {
+        let params = (mu, sigma, 0.0, 1.0);
+        let samples = (0..N).map(|_| truncated_normal.random(&mut rng, params)).collect::<Vec<f32>>();
+        assert!(samples.iter().all(|x| (0.0..=1.0).contains(x)));
+        if sigma == 0.0 {
+            assert!(samples.iter().all(|&x| x == mu));
+            continue;
+        }
+        let mean = samples.iter().map(|&x| x as f64).sum::<f64>() / N as f64;
+        let expected = integrate(|x| truncated_normal.logpdf(&x, params) + (x as f64).ln(), 0.0, 1.0, 100_000);
+        let var = integrate(|x| truncated_normal.logpdf(&x, params) + 2. * (x as f64 - expected).abs().ln(), 0.0, 1.0, 100_000);
+        assert!((mean - expected).abs() < 5. * (var / N as f64).sqrt() + 1e-4,
+            "sample mean {} != {} for {:?}", mean, expected, params);
+    }
+}
+
+#[test]
+fn test_outlier_posteriors() {
+    let params = (0.5, 0.1, 0.05);
+    assert!(noisy_pixel.outlier_posterior(&0.5, params) < 0.05);
+    assert!(noisy_pixel.outlier_posterior(&0.95, params) > 0.99);
+    assert_eq!(noisy_pixel.outlier_posterior(&0.95, (0.5, 0.0, 0.05)), 0.0);
+
+    // a color pixel is an outlier as soon as one of its channels is
+    let colors = vec![[0.5; 3]; AREA];
+    let mut corrupted = colors.clone();
+    corrupted[7] = [0.5, 0.5, 0.0];
+    let probs = noisy_colors.outlier_posteriors(&corrupted, (colors, 0.1, 0.05));
+    assert!(probs[7] > 0.99);
+    assert!(probs.iter().enumerate().all(|(i, &p)| i == 7 || p < 0.15));
+}
+
+#[test]
+fn test_heavy_tailed_normalized() {
+    for &(mu, scale, nu) in [(0.5, 0.1, 3.0), (0.02, 0.05, 1.0), (0.9, 0.5, 10.0), (30.0, 0.1, 4.0), (-8.0, 0.2, 2.0)].iter() {
+        let z = integrate(|x| truncated_laplace.logpdf(&x, (mu, scale, 0.0, 1.0)), 0.0, 1.0, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "laplace integral {} != 1 for {:?}", z, (mu, scale));
+        let z = integrate(|x| truncated_student_t.logpdf(&x, (mu, scale, nu, 0.0, 1.0)), 0.0, 1.0, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "student-t integral {} != 1 for {:?}", z, (mu, scale, nu));
+    }
+    for noise in [PixelNoise::Laplace, PixelNoise::StudentT(3.0)] {
+        let z = integrate(|x| NoisyPixel { noise }.logpdf(&x, (0.3, 0.1, 0.05)), 0.0, 1.0, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "integral {} != 1 for {:?}", z, noise);
+    }
+}
+
+#[test]
+fn test_heavy_tailed_samples() {
+    let mut rng = ThreadRng::default();
+    const N: usize = 20_000;
+    const BINS: usize = 10;
+    for &(mu, scale, nu) in [(0.5, 0.2, 3.0), (1.3, 0.3, 2.0), (-20.0, 0.5, 5.0)].iter() {
+        let laplace_params = (mu, scale, 0.0, 1.0);
+        let t_params = (mu, scale, nu, 0.0, 1.0);
+        let mut counts = [[0usize; BINS]; 2];
+        for _ in 0..N {
+            let xs = [truncated_laplace.random(&mut rng, laplace_params), truncated_student_t.random(&mut rng, t_params)];
+            for (count, x) in counts.iter_mut().zip(xs) {
+                assert!((0.0..=1.0).contains(&x));
+                count[((x * BINS as f32) as usize).min(BINS - 1)] += 1;
+            }
+        }
+        for i in 0..BINS {
+            let (a, b) = (i as f32 / BINS as f32, (i + 1) as f32 / BINS as f32);
+            let ps = [
+                integrate(|x| truncated_laplace.logpdf(&x, laplace_params), a, b, 10_000),
+                integrate(|x| truncated_student_t.logpdf(&x, t_params), a, b, 10_000)
+            ];
+            for (count, p) in counts.iter().zip(ps) {
+                let stderr = (N as f64 * p * (1. - p)).sqrt();
+                assert!((count[i] as f64 - N as f64 * p).abs() < 5. * stderr + 1.,
+                    "bin {}: {} samples, expected {} for {:?}", i, count[i], N as f64 * p, t_params);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_heavy_tailed_robustness() {
+    // score the true alignment of ball.bmp against a copy shifted 3 pixels to the right
+    let observation = load_colors("./tests/ball.bmp");
+    let shifted = (0..AREA).map(|i| observation[i - (i % W).min(3)]).collect::<Colors>();
+
+    // and occlude the middle of the pixels that tell them apart with a black block
+    let (mut cx, mut cy, mut n) = (0, 0, 0);
+    for i in 0..AREA {
+        if vec3_norm(&vec3_sub(observation[i], shifted[i])) > 0.2 {
+            (cx, cy, n) = (cx + i % W, cy + i / W, n + 1);
+        }
+    }
+    let (cx, cy) = (cx / n, cy / n);
+    let mut corrupted = observation.clone();
+    for y in cy - 12..cy + 12 {
+        for x in cx - 12..cx + 12 {
+            corrupted[y * W + x] = [0.0, 0.0, 0.0];
+        }
+    }
+
+    // without the uniform outlier component, heavy tails keep most of the evidence for the true alignment
+    let evidence_lost = |noise: PixelNoise| {
+        let likelihood = NoisyColors { noise, mask: Mask::All };
+        let margin = |o: &Colors| likelihood.logpdf(o, (observation.clone(), 0.0, 0.1))
+            - likelihood.logpdf(o, (shifted.clone(), 0.0, 0.1));
+        let (clean, occluded) = (margin(&observation), margin(&corrupted));
+        assert!(clean > 0.0 && occluded > 0.0, "{:?}: margins {} and {}", noise, clean, occluded);
+        (clean - occluded) / clean
+    };
+    let lost = [PixelNoise::Gaussian, PixelNoise::Laplace, PixelNoise::StudentT(3.0)].map(evidence_lost);
+    assert!(lost[1] < lost[0] && lost[2] < lost[0], "fraction of evidence lost: {:?}", lost);
+}
+
+#[test]
+fn test_sensor_pixel_normalized() {
+    for &(mu, dropout_prob, outlier_prob, sigma) in [(0.5, 0.02, 0.01, 0.01), (0.05, 0.5, 0.1, 0.1), (0.0, 1.0, 0.01, 1.0)].iter() {
+        let params = (mu, dropout_prob, outlier_prob, sigma);
+        let mass = sensor_pixel.logpdf(&0.0, params).exp();
+        let z = mass + integrate(|x| sensor_pixel.logpdf(&x, params), 0.0, 1.0, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "total probability {} != 1 for {:?}", z, params);
+    }
+
+    let mut rng = ThreadRng::default();
+    let params = (0.5, 0.3, 0.1, 0.05);
+    let zeros = (0..100_000).filter(|_| sensor_pixel.random(&mut rng, params) == 0.0).count();
+    assert!((zeros as f64 / 100_000. - 0.27).abs() < 0.01, "{} zeros", zeros);
+}
+
+#[test]
+fn test_depth_sensor_noise() {
+    let sensor = DepthSensor::default();
+    let lm = |z: f32| 1.0 - (z - NEAR) / (FAR - NEAR);
+
+    // the same 2cm error is much less surprising at 5m than at 1m
+    let (near, far) = (vec![lm(1.0); AREA], vec![lm(5.0); AREA]);
+    let params = (sensor.pixel_params(&near)[0], sensor.pixel_params(&far)[0]);
+    assert!(params.1.3 > 5.0 * params.0.3);
+    let w_near = sensor_pixel.logpdf(&lm(1.02), params.0) - sensor_pixel.logpdf(&lm(1.0), params.0);
+    let w_far = sensor_pixel.logpdf(&lm(5.02), params.1) - sensor_pixel.logpdf(&lm(5.0), params.1);
+    assert!(w_far > w_near + 5.0, "{} <= {}", w_far, w_near);
+
+    // a step in depth drops out more often than a flat surface, and misses never return a reading
+    let pixels = (0..AREA).map(|i| if i % W < W / 2 { lm(1.0) } else if i % W < W - 4 { lm(3.0) } else { 0.0 }).collect::<Depths>();
+    let params = sensor.pixel_params(&pixels);
+    assert_eq!(params[W / 2].1, sensor.edge_dropout_prob);
+    assert_eq!(params[W / 4].1, sensor.dropout_prob);
+    assert_eq!(params[W - 1].1, 1.0);
+    let missing = vec![0.0; AREA];
+    let w = sensor_depths.logpdf(&missing, (pixels.clone(), sensor));
+    assert!(w.is_finite());
+    let mut rng = ThreadRng::default();
+    let noisy = sensor_depths.random(&mut rng, (pixels, sensor));
+    let missing = (0..H).filter(|y| noisy[y * W + W - 1] == 0.0).count();
+    assert!(missing as f32 > 0.9 * H as f32, "{} of {} misses returned nothing", missing, H);
+}
+
+#[test]
+fn test_masked_likelihoods() {
+    let mut rng = ThreadRng::default();
+    let colors = (0..AREA).map(|i| [(i % 5) as f32 / 5.0, 0.5, 0.2]).collect::<Colors>();
+    let noisy = noisy_colors.random(&mut rng, (colors.clone(), 0.1, 0.05));
+    let roi = Rect { x0: 10, y0: 20, x1: 50, y1: 30 };
+    let likelihood = NoisyColors { noise: PixelNoise::Gaussian, mask: Mask::Roi(roi) };
+    let expected = (0..AREA)
+        .filter(|i| roi.contains(i % W, i / W))
+        .map(|i| (0..=2).map(|c| noisy_pixel.logpdf(&noisy[i][c], (colors[i][c], 0.1, 0.05))).sum::<f64>())
+        .sum::<f64>();
+    let w = likelihood.logpdf(&noisy, (colors.clone(), 0.1, 0.05));
+    assert!((w - expected).abs() < 1e-6 * expected.abs());
+
+    // excluded pixels contribute nothing, however wrong they are
+    let mut corrupted = noisy.clone();
+    corrupted[0] = [1.0, 0.0, 1.0];
+    assert_eq!(likelihood.logpdf(&corrupted, (colors.clone(), 0.1, 0.05)), w);
+
+    // the same pixels as a per-pixel mask, round-tripped through a bitmap
+    let path = std::env::temp_dir().join("roi_mask.bmp");
+    let path = path.to_str().unwrap();
+    let image = (0..AREA).map(|i| if roi.contains(i % W, i / W) { [1.0; 3] } else { [0.0; 3] }).collect::<Colors>();
+    save_colors(path, &image);
+    let mask = load_mask(path);
+    assert_eq!(mask.count(), roi.area());
+    let likelihood = NoisyColors { noise: PixelNoise::Gaussian, mask };
+    assert_eq!(likelihood.logpdf(&noisy, (colors, 0.1, 0.05)), w);
+
+    let depths = vec![0.5; AREA];
+    let none = NoisyDepths { noise: PixelNoise::Laplace, mask: Mask::Pixels(Arc::new(vec![false; AREA])) };
+    assert_eq!(none.logpdf(&vec![0.0; AREA], (depths, 0.1, 0.05)), 0.0);
+}
+
+#[test]
+fn test_noisy_rgbd_sums_observations() {
+    let mut rng = ThreadRng::default();
+    let colors = (0..AREA).map(|i| [(i % 3) as f32 / 3.0, 0.4, 0.7]).collect::<Colors>();
+    let depths = (0..AREA).map(|i| (i % 11) as f32 / 11.0).collect::<Depths>();
+    let pixels = (colors.clone(), depths.clone());
+    let noisy = noisy_rgbd.random(&mut rng, (pixels.clone(), 0.1, 0.05, 0.02, 0.01));
+    let expected = noisy_colors.logpdf(&noisy.0, (colors, 0.1, 0.05)) + noisy_depths.logpdf(&noisy.1, (depths, 0.02, 0.01));
+    let w = noisy_rgbd.logpdf(&noisy, (pixels, 0.1, 0.05, 0.02, 0.01));
+    assert!((w - expected).abs() < 1e-6 * expected.abs());
+}
+
+#[test]
+fn test_pyramid_likelihoods() {
+    let observation = load_colors("./tests/ball.bmp");
+    assert_eq!(downsample_colors(&observation, 0), observation);
+    let coarse = downsample_colors(&observation, 2);
+    assert_eq!(coarse.len(), AREA / 16);
+    let block_mean = (0..4).flat_map(|y| (0..4).map(move |x| y * W + x))
+        .fold(vec3_zero(), |c, i| vec3_add(c, observation[i]));
+    assert!(vec3_norm(&vec3_sub(coarse[0], vec3_scale(&block_mean, 1.0 / 16.0))) < 1e-5);
+    assert_eq!(downsample_depths(&vec![0.25; AREA], 3), vec![0.25; AREA / 64]);
+
+    // level 0 is the full resolution likelihood
+    let shifted = (0..AREA).map(|i| observation[i - (i % W).min(3)]).collect::<Colors>();
+    let full = PyramidColors { noise: PixelNoise::Gaussian, mask: Mask::All, level: 0 };
+    assert_eq!(full.logpdf(&observation, (shifted.clone(), 0.1, 0.1)), noisy_colors.logpdf(&observation, (shifted.clone(), 0.1, 0.1)));
+
+    // and coarser levels prefer the true alignment by a shrinking margin
+    let margins = (0..4).map(|level| {
+        let likelihood = PyramidColors { noise: PixelNoise::Gaussian, mask: Mask::All, level };
+        likelihood.logpdf(&observation, (observation.clone(), 0.1, 0.1)) - likelihood.logpdf(&observation, (shifted.clone(), 0.1, 0.1))
+    }).collect::<Vec<f64>>();
+    assert!(margins.windows(2).all(|m| m[0] > m[1] && m[1] > 0.0), "{:?}", margins);
+
+    // samples are noisy per block, so their noise shrinks as little as what is scored
+    let mut rng = ThreadRng::default();
+    let likelihood = PyramidColors { noise: PixelNoise::Gaussian, mask: Mask::All, level: 2 };
+    let noisy = likelihood.random(&mut rng, (observation.clone(), 0.0, 0.05));
+    let blocks = upsample_colors(&downsample_colors(&noisy, 2), 2);
+    assert!(blocks.iter().zip(noisy.iter()).all(|(a, b)| vec3_norm(&vec3_sub(*a, *b)) < 1e-5));
+    let coarse_noise = downsample_colors(&noisy, 2).iter().zip(coarse.iter())
+        .map(|(a, b)| vec3_norm(&vec3_sub(*a, *b)).powi(2))
+        .sum::<f32>() / (3 * coarse.len()) as f32;
+    assert!((coarse_noise.sqrt() - 0.05).abs() < 0.01, "{}", coarse_noise.sqrt());
+}
+
+#[test]
+fn test_sobel_edges() {
+    // a vertical step from black to white halfway across the image
+    let step = (0..AREA).map(|i| if i % W < W / 2 { 0.0 } else { 1.0 }).collect::<Depths>();
+    let (magnitudes, orientations) = sobel(&step);
+    let (y, x) = (H / 2, W / 2);
+    assert!((magnitudes[y*W + x] - 0.5f32.sqrt()).abs() < 1e-6);
+    assert_eq!(orientations[y*W + x], 0.0);
+    assert_eq!(magnitudes[y*W + 3], 0.0);
+
+    // the edge map of a color image follows its luma
+    let colors = step.iter().map(|&p| [p, p, p]).collect::<Colors>();
+    assert_eq!(edge_map(&colors).0, magnitudes);
+}
+
+#[test]
+fn test_von_mises() {
+    let mut rng = ThreadRng::default();
+    for &(mu, kappa) in [(0.0, 0.5), (1.0, 4.0), (3.0, 20.0), (-3.1, 100.0)].iter() {
+        let z = integrate(|x| von_mises.logpdf(&x, (mu, kappa)), -PI, PI, 200_000);
+        assert!((z - 1.0).abs() < 1e-3, "integral {} != 1 for {:?}", z, (mu, kappa));
+
+        // the mean resultant of the samples points at mu, also when the samples wrap around
+        let xs = (0..20_000).map(|_| von_mises.random(&mut rng, (mu, kappa))).collect::<Vec<f32>>();
+        assert!(xs.iter().all(|x| (-PI..=PI).contains(x)));
+        let (c, s) = xs.iter().fold((0.0, 0.0), |(c, s), x| (c + x.cos(), s + x.sin()));
+        assert!((s.atan2(c) - mu).sin().abs() < 0.05, "mean direction {} != {}", s.atan2(c), mu);
+    }
+}
+
+#[test]
+fn test_noisy_edges() {
+    let mut rng = ThreadRng::default();
+    let colors = load_colors("./tests/ball.bmp");
+    let edges = edge_map(&colors);
+    let noise = EdgeNoise::default();
+    let noisy = noisy_edges.random(&mut rng, (edges.clone(), noise));
+    let w = noisy_edges.logpdf(&noisy, (edges.clone(), noise));
+    assert!(w.is_finite());
+    assert!(noisy_edges.logpdf(&edges, (edges.clone(), noise)) > w);
+
+    // orientations ignore a global change of lighting, unlike pixels
+    let darker = colors.iter().map(|c| vec3_scale(c, 0.6)).collect::<Colors>();
+    let shifted = (0..AREA).map(|i| if i % W >= 3 { colors[i - 3] } else { colors[i] }).collect::<Colors>();
+    let orientation_only = EdgeNoise { sigma: 1e3, ..noise };
+    let score = |observed: &Colors| noisy_edges.logpdf(&edge_map(observed), (edges.clone(), orientation_only));
+    assert!(score(&darker) > score(&shifted), "{} {}", score(&darker), score(&shifted));
+}
+
+#[test]
+fn test_log_likelihood_maps() {
+    let mut rng = ThreadRng::default();
+    // few distinct true values, so most normalizers come from the cache
+    let colors = (0..AREA).map(|i| [(i % 5) as f32 / 5.0, 0.5, 0.2]).collect::<Colors>();
+    let noisy = noisy_colors.random(&mut rng, (colors.clone(), 0.1, 0.05));
+    let roi = Rect { x0: 10, y0: 20, x1: 50, y1: 30 };
+    for noise in [PixelNoise::Gaussian, PixelNoise::Laplace, PixelNoise::StudentT(3.0)] {
+        let pixel = NoisyPixel { noise };
+        let likelihood = NoisyColors { noise, mask: Mask::Roi(roi) };
+        let map = likelihood.log_likelihood_map(&noisy, (colors.clone(), 0.1, 0.05));
+        for i in 0..AREA {
+            let expected = if roi.contains(i % W, i / W) {
+                (0..=2).map(|c| pixel.logpdf(&noisy[i][c], (colors[i][c], 0.1, 0.05))).sum::<f64>()
+            } else {
+                0.0
+            };
+            assert!((map[i] - expected).abs() < 1e-9, "{:?} pixel {}: {} != {}", noise, i, map[i], expected);
+        }
+        assert_eq!(map.iter().sum::<f64>(), likelihood.logpdf(&noisy, (colors.clone(), 0.1, 0.05)));
+
+        let depths = colors.iter().map(|c| c[0]).collect::<Depths>();
+        let noisy_d = noisy.iter().map(|c| c[0]).collect::<Depths>();
+        let map = NoisyDepths { noise, mask: Mask::All }.log_likelihood_map(&noisy_d, (depths.clone(), 0.1, 0.05));
+        for i in 0..AREA {
+            assert!((map[i] - pixel.logpdf(&noisy_d[i], (depths[i], 0.1, 0.05))).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn test_plate_colors() {
+    let mut rng = ThreadRng::default();
+    let render = vec![[0.5, 0.4, 0.3]; AREA];
+    let plate = BackgroundPlate { colors: Arc::new((0..AREA).map(|i| [(i % 3) as f32 / 3.0, 0.8, 0.1]).collect()), outlier_prob: 0.05, sigma: 0.02 };
+    let roi = Rect { x0: 40, y0: 40, x1: 80, y1: 90 };
+    let likelihood = PlateColors { noise: PixelNoise::Gaussian, mask: Mask::All, plate: plate.clone() };
+    let params = (render.clone(), Mask::Roi(roi), 0.1, 0.1);
+    let noisy = likelihood.random(&mut rng, params.clone());
+
+    // the foreground is scored against the render and the rest against the plate, each with its own noise
+    let map = likelihood.log_likelihood_map(&noisy, params.clone());
+    for i in 0..AREA {
+        let (true_p, outlier_prob, sigma) = if roi.contains(i % W, i / W) {
+            (render[i], 0.1, 0.1)
+        } else {
+            (plate.colors[i], 0.05, 0.02)
+        };
+        let expected = (0..=2).map(|c| noisy_pixel.logpdf(&noisy[i][c], (true_p[c], outlier_prob, sigma))).sum::<f64>();
+        assert!((map[i] - expected).abs() < 1e-9);
+    }
+    assert_eq!(map.iter().sum::<f64>(), likelihood.logpdf(&noisy, params));
+
+    // samples off the foreground follow the plate
+    let off = (0..AREA).filter(|i| !roi.contains(i % W, i / W)).collect::<Vec<usize>>();
+    let mean_err = off.iter().map(|&i| (noisy[i][1] - 0.8).abs()).sum::<f32>() / off.len() as f32;
+    assert!(mean_err < 0.05, "{mean_err}");
+}
+
+#[test]
+fn test_summary_statistics() {
+    // a disk on a gray background, with its left half lit
+    let disk = |cx: f32, cy: f32| (0..AREA).map(|i| {
+        let (x, y) = ((i % W) as f32 - cx, (i / W) as f32 - cy);
+        if x.hypot(y) < 20.0 { if x < 0.0 { [0.9, 0.1, 0.1] } else { [0.1, 0.9, 0.1] } } else { [0.5, 0.5, 0.5] }
+    }).collect::<Colors>();
+    let pixels = disk(64.0, 40.0);
+
+    let histogram = SummaryStatistic::Histogram { bins: 4 }.summarize(&pixels, &Mask::All);
+    assert_eq!(histogram.len(), 12);
+    for c in 0..=2 {
+        assert!((histogram[4*c..4*c + 4].iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+    let means = SummaryStatistic::RegionMeans { rows: 2, cols: 2 }.summarize(&pixels, &Mask::All);
+    assert_eq!(means.len(), 12);
+    assert!(means[0] > means[3] && means[1] < means[4], "{:?}", means);
+    assert!(means[6..].iter().all(|v| (v - 0.5).abs() < 1e-6));
+
+    let moments = SummaryStatistic::SilhouetteMoments { threshold: 0.2 }.summarize(&pixels, &Mask::All);
+    assert!((moments[0] - PI * 400.0 / AREA as f32).abs() < 0.01, "{:?}", moments);
+    assert!((moments[1] - 0.5).abs() < 0.01 && (moments[2] - 40.5 / H as f32).abs() < 0.01);
+    assert!((moments[3] - moments[4]).abs() < 0.005 && moments[5].abs() < 0.05);
+
+    // the kernel is peaked at equal summaries, and the uniform one rejects distant ones
+    let abc_gaussian = AbcColors { stats: vec![SummaryStatistic::SilhouetteMoments { threshold: 0.2 }], kernel: AbcKernel::Gaussian, mask: Mask::All };
+    let shifted = disk(70.0, 40.0);
+    assert!(abc_gaussian.logpdf(&pixels, (pixels.clone(), 0.05)) > abc_gaussian.logpdf(&shifted, (pixels.clone(), 0.05)));
+    let abc_uniform = AbcColors { kernel: AbcKernel::Uniform, ..abc_gaussian.clone() };
+    assert!(abc_uniform.logpdf(&shifted, (pixels.clone(), 0.1)).is_finite());
+    assert_eq!(abc_uniform.logpdf(&shifted, (pixels.clone(), 0.01)), f64::NEG_INFINITY);
+
+    // simulated images are the render plus pixel noise at the tolerance, so their summaries stay close to its
+    let simulated = abc_gaussian.random(&mut ThreadRng::default(), (pixels.clone(), 0.05));
+    let rms = (simulated.iter().zip(pixels.iter())
+        .map(|(a, b)| (0..=2).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>()).sum::<f32>() / (3 * AREA) as f32).sqrt();
+    assert!((rms - 0.05).abs() < 0.005, "{}", rms);
+    let abc_means = AbcColors { stats: vec![SummaryStatistic::RegionMeans { rows: 2, cols: 2 }], ..abc_gaussian.clone() };
+    assert!(abc_means.logpdf(&simulated, (pixels.clone(), 0.05)) > abc_means.logpdf(&shifted, (pixels.clone(), 0.05)));
+
+    // the one-dimensional kernels are normalized
+    for kernel in [AbcKernel::Gaussian, AbcKernel::Uniform] {
+        let abc = AbcColors { kernel, ..abc_gaussian.clone() };
+        let z = integrate(|x| abc.kernel_logpdf(&[x], &[0.5], 0.1), -1.0, 2.0, 100_000);
+        assert!((z - 1.0).abs() < 1e-3, "{:?}: {}", kernel, z);
+    }
+}
@@ -0,0 +1,288 @@
+use std::f32::consts::PI;
+use modppl::prelude::*;
+use modppl_derender::*;
+
+
+fn random_rays(n: usize) -> RayPacket {
+    let mut rng = ThreadRng::default();
+    let mut rays = RayPacket::with_capacity(n);
+    for _ in 0..n {
+        let o = [
+            uniform.random(&mut rng, (-2.0, 2.0)) as f32,
+            uniform.random(&mut rng, (0.1, 2.0)) as f32,
+            uniform.random(&mut rng, (-2.0, 2.0)) as f32
+        ];
+        let mut d = [
+            normal.random(&mut rng, (0.0, 1.0)) as f32,
+            normal.random(&mut rng, (0.0, 1.0)) as f32,
+            normal.random(&mut rng, (0.0, 1.0)) as f32
+        ];
+        vec3_normalize(&mut d);
+        rays.push(o, d);
+    }
+    rays
+}
+
+#[test]
+fn test_packet_matches_scalar() {
+    let rays = random_rays(1000);
+    let solids: Vec<Box<dyn Solid>> = vec![
+        Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }),
+        Box::new(Sphere { center: [0.3, 0.5, -0.2], radius: 0.5 }),
+        Box::new(Cuboid { center: [0.0, 0.4, 0.3], half_extents: [0.3, 0.4, 0.2], rotation: quat_from_rotvec([0.0, 0.5, 0.0]) }),
+        Box::new(Cylinder { center: [-0.5, 0.3, 0.0], radius: 0.4, half_height: 0.3, rotation: quat_from_rotvec([0.3, 0.0, 0.2]) })
+    ];
+
+    for s in solids.iter() {
+        let mut hits = PacketHits::new(rays.len());
+        s.ray_intersect_reflect_packet(&rays, &mut hits);
+        let mut distances = vec![0.0; rays.len()];
+        s.ray_intersect_packet(&rays, &mut distances);
+
+        for (i, &distance) in distances.iter().enumerate() {
+            match s.ray_intersect_reflect(rays.origin(i), rays.dir(i)) {
+                Some((d, n)) => {
+                    assert_eq!(d, hits.distances[i]);
+                    assert_eq!(d, distance);
+                    assert!(vec3_norm(&vec3_sub(n, hits.normal(i))) < 1e-4);
+                }
+                None => {
+                    assert_eq!(hits.distances[i], f32::INFINITY);
+                    assert_eq!(distance, f32::INFINITY);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_packet_depths_match_scalar() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let mut scene: Scene = vec![
+        (Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>, [0.5; 3]),
+        (Box::new(Sphere { center: [0.2, 0.5, -1.0], radius: 0.5 }) as Box<dyn Solid>, [0.5; 3])
+    ];
+
+    // each pixel sees the nearest solid, whichever order the scene lists them in
+    let iso = pose_to_mat4(x);
+    for _ in 0..2 {
+        let mut pixels = vec![0.0; AREA];
+        raytrace_depths(x, &camera, &scene, &mut pixels);
+        for y in (0..H).step_by(7) {
+            for x in (0..W).step_by(5) {
+                let (o, d) = camera.unproject([x as f32, (H - y) as f32]).unwrap();
+                let o = mat4_mulv(iso, [o[0], o[1], o[2], 1.0]);
+                let d = mat4_mulv(iso, [d[0], d[1], d[2], 0.0]);
+                let nearest = scene.iter()
+                    .filter_map(|s| s.0.ray_intersect([o[0], o[1], o[2]], [d[0], d[1], d[2]]))
+                    .fold(f32::INFINITY, f32::min);
+                let expected = if (NEAR..=FAR).contains(&nearest) { 1.0 - (nearest - NEAR) / (FAR - NEAR) } else { 0.0 };
+                assert!((pixels[y * W + x] - expected).abs() < 1e-5);
+            }
+        }
+        scene.reverse();
+    }
+}
+
+#[test]
+fn test_cuboid_and_cylinder() {
+    let cuboid = Cuboid { center: [0.0, 0.5, 0.0], half_extents: [0.5, 0.5, 0.2], rotation: quat_id() };
+    let turned = Cuboid { half_extents: [0.4, 0.5, 0.4], rotation: quat_from_rotvec([0.0, PI/4.0, 0.0]), ..cuboid.clone() };
+    let cylinder = Cylinder { center: [0.0, 0.5, 0.0], radius: 0.3, half_height: 0.5, rotation: quat_id() };
+
+    // head-on hits on a face, a side and a cap, with outward unit normals
+    let (d, n) = cuboid.ray_intersect_reflect([0.0, 0.5, 2.0], [0.0, 0.0, -1.0]).unwrap();
+    assert!((d - 1.8).abs() < 1e-5 && vec3_norm(&vec3_sub(n, [0.0, 0.0, 1.0])) < 1e-5);
+    let (d, n) = cylinder.ray_intersect_reflect([2.0, 0.5, 0.0], [-1.0, 0.0, 0.0]).unwrap();
+    assert!((d - 1.7).abs() < 1e-5 && vec3_norm(&vec3_sub(n, [1.0, 0.0, 0.0])) < 1e-5);
+    let (d, n) = cylinder.ray_intersect_reflect([0.1, 3.0, 0.0], [0.0, -1.0, 0.0]).unwrap();
+    assert!((d - 2.0).abs() < 1e-5 && vec3_norm(&vec3_sub(n, [0.0, 1.0, 0.0])) < 1e-5);
+
+    // turning the cuboid turns its faces: an edge of its square footprint now points at the viewer
+    let (d, n) = turned.ray_intersect_reflect([0.0, 0.5, 2.0], [0.0, 0.0, -1.0]).unwrap();
+    assert!((d - (2.0 - 0.4 * 2.0f32.sqrt())).abs() < 1e-4, "{}", d);
+    assert!((vec3_norm(&n) - 1.0).abs() < 1e-5 && n[2] > 0.0);
+
+    // from inside, rays leave through the far side
+    let (d, n) = cuboid.ray_intersect_reflect([0.0, 0.5, 0.0], [1.0, 0.0, 0.0]).unwrap();
+    assert!((d - 0.5).abs() < 1e-5 && vec3_norm(&vec3_sub(n, [1.0, 0.0, 0.0])) < 1e-5);
+
+    // rays in the plane of a face graze it
+    let (d, n) = cuboid.ray_intersect_reflect([0.5, 1.0, 2.0], [0.0, 0.0, -1.0]).unwrap();
+    assert!((d - 1.8).abs() < 1e-5 && vec3_norm(&vec3_sub(n, [0.0, 0.0, 1.0])) < 1e-5);
+
+    // and rays past the edges, above the caps, or pointing away miss
+    assert!(cuboid.ray_intersect([0.6, 0.5, 2.0], [0.0, 0.0, -1.0]).is_none());
+    assert!(cylinder.ray_intersect([0.25, 0.5, 2.0], [1.0, 0.0, -1.0]).is_none());
+    assert!(cylinder.ray_intersect([2.0, 1.1, 0.0], [-1.0, 0.0, 0.0]).is_none());
+    assert!(cylinder.ray_intersect([2.0, 0.5, 0.0], [1.0, 0.0, 0.0]).is_none());
+}
+
+fn ball_scene(u: f32, v: f32, radius: f32) -> Scene {
+    vec![
+        (Box::new(Plane { origin: vec3_zero(), normal: [0.0, 1.0, 0.0] }) as Box<dyn Solid>, [0.6, 0.5, 0.4]),
+        (Box::new(Sphere { center: [u, radius, v], radius }) as Box<dyn Solid>, [0.2, 0.3, 0.9])
+    ]
+}
+
+fn mean_abs_diff(a: &Colors, b: &Colors) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| vec3_norm(&vec3_sub(*a, *b))).sum::<f32>() / AREA as f32
+}
+
+#[test]
+fn test_light_radius() {
+    // the cap of sky a sphere subtends at its light radius is the tolerated fraction of the sky
+    for tolerance in [0.01, 0.05, 0.2] {
+        let d = light_radius(0.4, tolerance);
+        let cos_a = (1.0 - (0.4 / d).powi(2)).sqrt();
+        assert!((2.0 * (1.0 - cos_a) - tolerance).abs() < 1e-4 * tolerance.max(1.0), "{} {}", tolerance, d);
+    }
+    assert_eq!(light_radius(0.4, 0.0), f32::INFINITY);
+    assert_eq!(light_radius(0.4, 2.0), 0.4);
+
+    // a ball changes the light reaching the table by over the tolerance two radii from where it touches it, but by
+    // less beyond its light radius
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let iso_inv = mat4_inv(pose_to_mat4(x));
+    let brightness = |scene: &Scene, p: Vec3| {
+        let c = mat4_mulv(iso_inv, [p[0], p[1], p[2], 1.0]);
+        let uv = camera.project([c[0], c[1], c[2]]).unwrap();
+        let (px, py) = ((uv[0] - 0.5).floor() as usize, (H as f32 - uv[1] + 0.5).floor() as usize);
+        let rect = Rect { x0: px - 1, y0: py - 1, x1: px + 2, y1: py + 2 };
+        let mut pixels = vec![[0.0; 3]; AREA];
+        let mut total = 0.0;
+        for _ in 0..200 {
+            raytrace_colors_rect(x, &camera, scene, [1.0; 3], rect, &mut pixels);
+            total += (0..9).map(|i| pixels[(rect.y0 + i / 3) * W + rect.x0 + i % 3][0].powi(2)).sum::<f32>();
+        }
+        total
+    };
+    let (r, tolerance) = (0.4, 0.05);
+    let change = |d: f32| {
+        let p = [(d * d - r * r).sqrt(), 0.0, -0.5];
+        let mut table = ball_scene(0.0, -0.5, r);
+        table.truncate(1);
+        (1.0 - brightness(&ball_scene(0.0, -0.5, r), p) / brightness(&table, p)).abs()
+    };
+    let near = change((5.0f32).sqrt() * r);
+    let far = change(light_radius(r, tolerance));
+    assert!(near > tolerance && far < tolerance, "{} {}", near, far);
+}
+
+#[test]
+fn test_sphere_rect() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let pinhole = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    assert!(sphere_rect(x, &pinhole, [0.0, 0.4, -0.5], 0.4).is_some());
+
+    // a sphere reaching behind the camera, or seen by a camera that bends lines, can't be bounded on screen
+    assert!(sphere_rect(x, &pinhole, [0.0, 1.5, 1.2], 0.4).is_none());
+    assert!(sphere_rect(x, &pinhole, [0.0, 0.4, -0.5], light_radius(0.4, 0.0)).is_none());
+    let fisheye = FisheyeCamera::new(FisheyeModel::Equidistant, PI, NEAR);
+    assert!(sphere_rect(x, &fisheye, [0.0, 0.4, -0.5], 0.4).is_none());
+    let mut distorted = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    distorted.distortion = Distortion { k1: -0.2, ..Distortion::default() };
+    assert!(sphere_rect(x, &distorted, [0.0, 0.4, -0.5], 0.4).is_none());
+    let mut cache = ColorRenderCache::new(Box::new(distorted));
+    cache.render(x, ball_scene(0.0, -0.5, 0.1), [0.9, 1.0, 1.0]);
+    assert_eq!(cache.render(x, ball_scene(0.1, -0.6, 0.1), [0.9, 1.0, 1.0]).1, Rect::full());
+}
+
+#[test]
+fn test_render_cache() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let camera = || Box::new(ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR));
+    let background_color = [0.9, 1.0, 1.0];
+
+    let mut cache = ColorRenderCache::new(camera());
+    let (prev, rect) = cache.render(x, ball_scene(0.0, -0.5, 0.1), background_color);
+    let prev = prev.clone();
+    assert_eq!(rect, Rect::full());
+    assert!(cache.render(x, ball_scene(0.0, -0.5, 0.1), background_color).1.is_empty());
+
+    // moving a small ball only re-renders the pixels around its old and new positions
+    let (pixels, rect) = cache.render(x, ball_scene(0.1, -0.6, 0.1), background_color);
+    let pixels = pixels.clone();
+    assert!(!rect.is_empty() && rect.area() < AREA / 2, "{:?}", rect);
+    for y in 0..H {
+        for x in 0..W {
+            if !rect.contains(x, y) {
+                assert_eq!(pixels[y * W + x], prev[y * W + x]);
+            }
+        }
+    }
+
+    // and agrees with a full render up to Monte Carlo noise
+    let mut fresh1 = vec![[0.0; 3]; AREA];
+    let mut fresh2 = vec![[0.0; 3]; AREA];
+    raytrace_colors(x, camera().as_ref(), &ball_scene(0.1, -0.6, 0.1), background_color, &mut fresh1);
+    raytrace_colors(x, camera().as_ref(), &ball_scene(0.1, -0.6, 0.1), background_color, &mut fresh2);
+    let noise = mean_abs_diff(&fresh1, &fresh2);
+    assert!(mean_abs_diff(&pixels, &fresh1) < 1.5 * noise, "{} >= 1.5 * {}", mean_abs_diff(&pixels, &fresh1), noise);
+
+    // a big ball darkens the table well beyond twice its radius, so moving it re-renders much more
+    cache.render(x, ball_scene(0.0, -0.5, 0.4), background_color);
+    let rect = cache.render(x, ball_scene(0.1, -0.6, 0.4), background_color).1;
+    assert!(rect.area() > AREA / 2, "{:?}", rect);
+
+    // changing the unbounded table re-renders everything
+    let mut scene = ball_scene(0.1, -0.6, 0.4);
+    scene[0].1 = [0.1, 0.1, 0.1];
+    assert_eq!(cache.render(x, scene, background_color).1, Rect::full());
+}
+
+/// a solid implementing only what the trait requires
+struct Floor;
+
+impl Solid for Floor {
+    fn ray_intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<f32> {
+        self.ray_intersect_reflect(ray_origin, ray_dir).map(|(d, _)| d)
+    }
+
+    fn ray_intersect_reflect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<(f32,Vec3)> {
+        let d = -ray_origin[1] / ray_dir[1];
+        if d > 1e-6 { Some((d, [0.0, 1.0, 0.0])) } else { None }
+    }
+}
+
+#[test]
+fn test_minimal_solid() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let camera = Box::new(ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR));
+    let scene = || vec![(Box::new(Floor) as Box<dyn Solid>, [0.6, 0.5, 0.4])];
+    assert!(Floor.bounds().is_none());
+    assert!(!Floor.same_as(&Floor));
+
+    // without bounds or a way to compare it, the cache can't tell what changed, so it re-renders everything
+    let mut cache = ColorRenderCache::new(camera);
+    assert_eq!(cache.render(x, scene(), [0.9, 1.0, 1.0]).1, Rect::full());
+    assert_eq!(cache.render(x, scene(), [0.9, 1.0, 1.0]).1, Rect::full());
+}
+
+#[test]
+fn test_coverage() {
+    let x = vec3_euler_to_pose([0.0, 1.5, 1.2], [-0.3, 0.0, 0.0]);
+    let camera = ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR);
+    let scene = ball_scene(0.0, -0.5, 0.4);
+
+    // the ball rests on the table in front of the camera, so it covers exactly the pixels it's hit at
+    let ball = raytrace_coverage(x, &camera, &scene, &[false, true]);
+    let mut ball_depths = vec![0.0; AREA];
+    raytrace_depths(x, &camera, &ball_scene(0.0, -0.5, 0.4).split_off(1), &mut ball_depths);
+    assert!(ball.count() > 0);
+    for (i, d) in ball_depths.iter().enumerate() {
+        assert_eq!(ball.contains(i % W, i / W), *d > 0.0);
+    }
+
+    // the table covers the rest of the lower half of the image
+    let table = raytrace_coverage(x, &camera, &scene, &[true, false]);
+    assert!((0..AREA).all(|i| !(ball.contains(i % W, i / W) && table.contains(i % W, i / W))));
+    assert!(table.contains(W / 2, H - 1) && !table.contains(W / 2, 0));
+
+    // and the render cache reports the coverage of its last scene
+    let mut cache = ColorRenderCache::new(Box::new(ProjectiveCamera::perspective(PI/2.0, W as f32/H as f32, NEAR, FAR)));
+    cache.render(x, ball_scene(0.0, -0.5, 0.4), [0.9, 1.0, 1.0]);
+    assert_eq!(cache.coverage(&[false, true]), ball);
+}